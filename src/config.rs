@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::Context;
+use rusty_interaction::types::Snowflake;
+use serde::Deserialize;
+
+const DEFAULT_CONFIG_PATH: &str = "./picturebot.toml";
+
+/// All configuration of the bot, read from the config file and overridden by environment variables.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub discord: DiscordConfig,
+    pub upload: UploadConfig,
+    pub ledger: LedgerConfig,
+    pub access: AccessConfig,
+    pub quota: QuotaConfig,
+    pub moderation: ModerationConfig,
+    /// Per-guild overrides, keyed by guild id.
+    pub guilds: HashMap<String, GuildConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DiscordConfig {
+    pub app_id: Option<Snowflake>,
+    pub public_key: Option<String>,
+    pub token: Option<String>,
+    pub owner_id: Option<Snowflake>,
+    pub webhooks: Option<Vec<String>>,
+    pub webhook_logo_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UploadConfig {
+    pub provider: Option<String>,
+    pub frontend_url: Option<String>,
+    pub frontend_url_max_length: Option<usize>,
+    /// Allowed file extensions, an empty map allows all extensions.
+    pub extensions: HashMap<String, ExtensionConfig>,
+    /// Maps file extensions to the extension they are converted to.
+    pub conversions: HashMap<String, String>,
+    pub deduplication: Option<bool>,
+    pub strip_metadata: Option<bool>,
+    pub thumbnail_widths: Option<Vec<u32>>,
+    pub thumbnail_name_template: Option<String>,
+    /// Storage path of uploads, see [crate::upload::naming::render] for the placeholders.
+    pub name_template: Option<String>,
+    /// Store uploads under short random names instead of [UploadConfig::name_template].
+    pub random_names: Option<bool>,
+    /// `reject` to refuse file names with unsupported characters, or `slugify` to replace them.
+    pub name_normalization: Option<String>,
+    pub webp_quality: Option<u8>,
+    /// Largest file fetched through the `url` option of `/upload`, in bytes.
+    pub remote_max_size: Option<u64>,
+    /// Timeout for fetching through the `url` option of `/upload`, in seconds.
+    pub remote_timeout: Option<u64>,
+    pub http_bearer: HttpBearerConfig,
+    pub s3: S3Config,
+    pub filesystem: FilesystemConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct ExtensionConfig {
+    pub max_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct HttpBearerConfig {
+    pub url: Option<String>,
+    pub auth_header_name: Option<String>,
+    pub auth_header_value: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct S3Config {
+    pub region: Option<String>,
+    pub url: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub security_token: Option<String>,
+    pub session_token: Option<String>,
+    pub profile: Option<String>,
+    pub bucket_name: Option<String>,
+    pub use_path_style: Option<bool>,
+    pub storage_path: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    pub acl: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct FilesystemConfig {
+    pub storage_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GuildConfig {
+    pub extensions: Option<HashMap<String, ExtensionConfig>>,
+    pub storage_prefix: Option<String>,
+    pub webhooks: Option<Vec<String>>,
+    pub access: Option<AccessConfig>,
+}
+
+/// Who may use `/upload`. Empty allow-lists allow everyone who is not denied.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AccessConfig {
+    pub allowed_roles: Vec<Snowflake>,
+    pub denied_roles: Vec<Snowflake>,
+    pub allowed_users: Vec<Snowflake>,
+    pub denied_users: Vec<Snowflake>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    pub uploads_per_hour: Option<u32>,
+    pub bytes_per_day: Option<u64>,
+    /// Limits for members with a role, keyed by role id. Unset limits fall back to the global ones.
+    pub roles: HashMap<String, QuotaLimitsConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct QuotaLimitsConfig {
+    pub uploads_per_hour: Option<u32>,
+    pub bytes_per_day: Option<u64>,
+}
+
+/// Holds uploads in a local staging directory until a moderator approves them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ModerationConfig {
+    pub enabled: Option<bool>,
+    /// Webhook of the moderator channel, it must be owned by the application to show buttons.
+    pub webhook: Option<String>,
+    pub staging_path: Option<String>,
+    /// Roles that may review uploads in addition to members with the Manage Server permission.
+    pub moderator_roles: Vec<Snowflake>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LedgerConfig {
+    pub database_path: Option<String>,
+}
+
+impl Config {
+    /// Loads the config file given by `PICTUREBOT_CONFIG`, or `./picturebot.toml` if it exists, and applies environment overrides.
+    pub fn load() -> anyhow::Result<Self> {
+        let mut config = match env::var("PICTUREBOT_CONFIG").ok() {
+            Some(path) => Config::read(PathBuf::from(path))?,
+            None => {
+                let path = PathBuf::from(DEFAULT_CONFIG_PATH);
+                if path.exists() {
+                    Config::read(path)?
+                } else {
+                    log::debug!("No config file found, using environment only");
+                    Config::default()
+                }
+            }
+        };
+
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    fn read(path: PathBuf) -> anyhow::Result<Self> {
+        log::info!("Reading config file {}", path.display());
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&content)
+                .with_context(|| format!("Failed to parse config file {}", path.display())),
+            _ => toml::from_str(&content)
+                .with_context(|| format!("Failed to parse config file {}", path.display())),
+        }
+    }
+
+    fn apply_env(&mut self) -> anyhow::Result<()> {
+        let discord = &mut self.discord;
+        override_parsed(&mut discord.app_id, "DISCORD_APP_ID")?;
+        override_string(&mut discord.public_key, "DISCORD_PUBLIC_KEY");
+        override_string(&mut discord.token, "DISCORD_TOKEN");
+        override_parsed(&mut discord.owner_id, "DISCORD_BOT_OWNER_ID")?;
+        if let Ok(value) = env::var("DISCORD_WEBHOOK_URLS") {
+            discord.webhooks = Some(value.split(',').map(|s| s.to_string()).collect());
+        }
+        override_string(&mut discord.webhook_logo_url, "DISCORD_WEBHOOK_LOGO_URL");
+
+        let upload = &mut self.upload;
+        override_string(&mut upload.provider, "UPLOAD_PROVIDER");
+        override_string(&mut upload.frontend_url, "UPLOAD_FRONTEND_URL");
+        override_parsed(&mut upload.frontend_url_max_length, "FRONTEND_URL_MAX_LENGTH")?;
+        if let Ok(value) = env::var("DISCORD_ALLOWED_FILE_EXTENSIONS") {
+            upload.extensions = parse_extensions(&value)?;
+        }
+        if let Ok(value) = env::var("DISCORD_CONVERT_FILE_EXTENSIONS") {
+            upload.conversions = parse_conversions(&value)?;
+        }
+        override_parsed(&mut upload.deduplication, "UPLOAD_DEDUPLICATION")?;
+        override_parsed(&mut upload.strip_metadata, "DISCORD_STRIP_IMAGE_METADATA")?;
+        if let Ok(value) = env::var("UPLOAD_THUMBNAIL_WIDTHS") {
+            upload.thumbnail_widths = Some(value.split(',')
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| s.trim().parse::<u32>())
+                .collect::<Result<Vec<u32>, _>>()
+                .context("Failed to parse UPLOAD_THUMBNAIL_WIDTHS")?);
+        }
+        override_string(&mut upload.thumbnail_name_template, "UPLOAD_THUMBNAIL_NAME_TEMPLATE");
+        override_string(&mut upload.name_template, "UPLOAD_NAME_TEMPLATE");
+        override_string(&mut upload.name_normalization, "UPLOAD_NAME_NORMALIZATION");
+        override_parsed(&mut upload.random_names, "UPLOAD_RANDOM_NAMES")?;
+        override_parsed(&mut upload.webp_quality, "UPLOAD_WEBP_QUALITY")?;
+        override_parsed(&mut upload.remote_max_size, "UPLOAD_REMOTE_MAX_SIZE")?;
+        override_parsed(&mut upload.remote_timeout, "UPLOAD_REMOTE_TIMEOUT")?;
+
+        let http_bearer = &mut upload.http_bearer;
+        override_string(&mut http_bearer.url, "UPLOAD_URL");
+        override_string(&mut http_bearer.auth_header_name, "UPLOAD_AUTH_HEADER_NAME");
+        override_string(&mut http_bearer.auth_header_value, "UPLOAD_AUTH_HEADER_VALUE");
+
+        let s3 = &mut upload.s3;
+        override_string(&mut s3.region, "S3_REGION");
+        override_string(&mut s3.url, "S3_URL");
+        override_string(&mut s3.access_key_id, "S3_ACCESS_KEY_ID");
+        override_string(&mut s3.secret_access_key, "S3_SECRET_ACCESS_KEY");
+        override_string(&mut s3.security_token, "S3_SECURITY_TOKEN");
+        override_string(&mut s3.session_token, "S3_SESSION_TOKEN");
+        override_string(&mut s3.profile, "S3_PROFILE");
+        override_string(&mut s3.bucket_name, "S3_BUCKET_NAME");
+        override_parsed(&mut s3.use_path_style, "S3_USE_PATH_STYLE")?;
+        override_string(&mut s3.storage_path, "S3_STORAGE_PATH");
+        override_string(&mut s3.cache_control, "S3_CACHE_CONTROL");
+        override_string(&mut s3.content_disposition, "S3_CONTENT_DISPOSITION");
+        override_string(&mut s3.acl, "S3_ACL");
+
+        override_string(&mut upload.filesystem.storage_path, "UPLOAD_STORAGE_PATH");
+
+        override_string(&mut self.ledger.database_path, "LEDGER_DATABASE_PATH");
+
+        let access = &mut self.access;
+        override_ids(&mut access.allowed_roles, "DISCORD_ALLOWED_ROLES")?;
+        override_ids(&mut access.denied_roles, "DISCORD_DENIED_ROLES")?;
+        override_ids(&mut access.allowed_users, "DISCORD_ALLOWED_USERS")?;
+        override_ids(&mut access.denied_users, "DISCORD_DENIED_USERS")?;
+
+        override_parsed(&mut self.quota.uploads_per_hour, "UPLOAD_QUOTA_UPLOADS_PER_HOUR")?;
+        override_parsed(&mut self.quota.bytes_per_day, "UPLOAD_QUOTA_BYTES_PER_DAY")?;
+
+        let moderation = &mut self.moderation;
+        override_parsed(&mut moderation.enabled, "MODERATION_ENABLED")?;
+        override_string(&mut moderation.webhook, "MODERATION_WEBHOOK_URL");
+        override_string(&mut moderation.staging_path, "MODERATION_STAGING_PATH");
+        override_ids(&mut moderation.moderator_roles, "MODERATION_MODERATOR_ROLES")?;
+
+        Ok(())
+    }
+}
+
+fn override_string(target: &mut Option<String>, name: &str) {
+    if let Ok(value) = env::var(name) {
+        *target = Some(value);
+    }
+}
+
+fn override_parsed<T>(target: &mut Option<T>, name: &str) -> anyhow::Result<()>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Ok(value) = env::var(name) {
+        *target = Some(value.parse::<T>().with_context(|| format!("Failed to parse {name}"))?);
+    }
+
+    Ok(())
+}
+
+/// Parses a comma separated list of ids.
+fn override_ids(target: &mut Vec<Snowflake>, name: &str) -> anyhow::Result<()> {
+    if let Ok(value) = env::var(name) {
+        *target = value.split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().parse::<Snowflake>())
+            .collect::<Result<Vec<Snowflake>, _>>()
+            .with_context(|| format!("Failed to parse {name}"))?;
+    }
+
+    Ok(())
+}
+
+/// Parses `png=1000000,jpg,...` into allowed extensions with optional size limits.
+fn parse_extensions(value: &str) -> anyhow::Result<HashMap<String, ExtensionConfig>> {
+    let mut map = HashMap::with_capacity(value.split(',').count());
+    for file_type in value.split(',') {
+        match file_type.split_once('=') {
+            None => {
+                map.insert(file_type.to_string(), ExtensionConfig::default());
+            },
+            Some((ext, length_str)) => {
+                let length = length_str.parse::<usize>().context("Failed to parse max length")?;
+                map.insert(ext.to_string(), ExtensionConfig { max_size: Some(length) });
+            }
+        }
+    }
+
+    Ok(map)
+}
+
+/// Parses `png=webp,jpg=keep,...` into conversion rules.
+fn parse_conversions(value: &str) -> anyhow::Result<HashMap<String, String>> {
+    let mut map = HashMap::new();
+    for rule in value.split(',').filter(|s| !s.is_empty()) {
+        let (ext, target) = rule.split_once('=')
+            .with_context(|| format!("Invalid conversion rule: {rule}"))?;
+        map.insert(ext.to_string(), target.to_string());
+    }
+
+    Ok(map)
+}
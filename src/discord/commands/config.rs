@@ -0,0 +1,187 @@
+use rusty_interaction::{defer, slash_command};
+use rusty_interaction::handler::InteractionHandler;
+use rusty_interaction::types::interaction::{Context, InteractionResponse};
+use rusty_interaction::types::Snowflake;
+
+use crate::discord;
+use crate::discord::BotInfo;
+use crate::discord::profile::GuildProfiles;
+use crate::discord::runtime::SharedConfig;
+use crate::discord::webhook::Webhook;
+use crate::ledger::UploadLedger;
+
+const WEBHOOK_URL_PREFIXES: [&str; 2] = ["https://discord.com/api/webhooks/", "https://discordapp.com/api/webhooks/"];
+
+#[defer]
+#[slash_command]
+pub(crate) async fn config_command(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    let bot = handler.data.get::<BotInfo>().unwrap();
+
+    let guild_id = match ctx.interaction.guild_id {
+        Some(id) => id,
+        None => {
+            return ctx.respond()
+                .content("This command can only be used in a server")
+                .is_ephemeral(true)
+                .finish();
+        }
+    };
+
+    if ctx.author_id != Some(bot.owner_id) && !discord::can_manage_guild(&ctx) {
+        return ctx.respond()
+            .content("Only members with the Manage Server permission can use this command")
+            .is_ephemeral(true)
+            .finish();
+    }
+
+    let data = &ctx.interaction.data.clone().unwrap();
+    let subcommand = data.options.clone().unwrap().into_iter().next().expect("No subcommand provided");
+    let opts = subcommand.options.clone().unwrap_or_default();
+    let option = |name: &str| opts.iter().find(|o| o.name == name).map(|o| o.value.trim().to_string());
+
+    let result = match subcommand.name.as_str() {
+        "allow-extension" => allow_extension(handler, guild_id, option("extension"), option("max-size")),
+        "set-max-size" => set_max_size(handler, guild_id, option("extension"), option("max-size")),
+        "set-prefix" => set_prefix(handler, guild_id, option("prefix")),
+        "set-webhook" => set_webhook(handler, guild_id, option("url")),
+        name => Err(format!("Unknown subcommand `{name}`")),
+    };
+
+    let message = match result {
+        Ok(message) => match reload_profiles(handler) {
+            Ok(_) => {
+                log::info!("User {:?} changed settings of guild {guild_id}: {message}", ctx.author_id);
+                message
+            }
+            Err(e) => {
+                log::error!("Failed to reload guild profiles: {e:#}");
+                format!("Saved, but failed to apply the new settings: {e}")
+            }
+        },
+        Err(message) => message,
+    };
+
+    ctx.respond()
+        .content(message)
+        .is_ephemeral(true)
+        .finish()
+}
+
+fn allow_extension(handler: &InteractionHandler, guild_id: Snowflake, extension: Option<String>, max_size: Option<String>) -> Result<String, String> {
+    let extension = parse_extension(extension)?;
+    let max_size = max_size.map(|s| parse_max_size(&s)).transpose()?;
+
+    let runtime = handler.data.get::<SharedConfig>().unwrap().current();
+    let global = &runtime.profiles.global().validator;
+    if !global.allows(&extension) {
+        return Err(format!("`.{extension}` files are not allowed by the bot configuration"));
+    }
+    check_global_limit(global.max_size(&extension), max_size)?;
+    let max_size = max_size.or(global.max_size(&extension));
+
+    store(handler.data.get::<UploadLedger>().unwrap().set_guild_extension(guild_id, &extension, max_size))?;
+
+    Ok(match max_size {
+        Some(max_size) => format!("Allowed `.{extension}` files up to {}", human_bytes::human_bytes(max_size as f64)),
+        None => format!("Allowed `.{extension}` files"),
+    })
+}
+
+fn set_max_size(handler: &InteractionHandler, guild_id: Snowflake, extension: Option<String>, max_size: Option<String>) -> Result<String, String> {
+    let extension = parse_extension(extension)?;
+    let max_size = parse_max_size(&max_size.ok_or("No maximum size provided")?)?;
+
+    let runtime = handler.data.get::<SharedConfig>().unwrap().current();
+    let profile = runtime.profiles.get(Some(guild_id));
+    if !profile.validator.allows(&extension) {
+        return Err(format!("`.{extension}` files are not allowed in this server, use `/config allow-extension` first"));
+    }
+    check_global_limit(runtime.profiles.global().validator.max_size(&extension), Some(max_size))?;
+
+    store(handler.data.get::<UploadLedger>().unwrap().set_guild_extension(guild_id, &extension, Some(max_size)))?;
+
+    Ok(format!("Limited `.{extension}` files to {}", human_bytes::human_bytes(max_size as f64)))
+}
+
+fn set_prefix(handler: &InteractionHandler, guild_id: Snowflake, prefix: Option<String>) -> Result<String, String> {
+    let prefix = prefix
+        .map(|p| p.trim_matches('/').to_ascii_lowercase())
+        .filter(|p| !p.is_empty());
+
+    if let Some(prefix) = &prefix {
+        if !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '/') {
+            return Err("Storage prefix may only contain letters, digits, `_` and `/`".to_string());
+        }
+        if prefix.split('/').any(|segment| segment.is_empty()) {
+            return Err("Storage prefix must not contain empty path segments".to_string());
+        }
+    }
+
+    store(handler.data.get::<UploadLedger>().unwrap().set_guild_storage_prefix(guild_id, prefix.as_deref()))?;
+
+    Ok(match prefix {
+        Some(prefix) => format!("New uploads are stored under `{prefix}/`"),
+        None => "Removed the storage prefix set for this server".to_string(),
+    })
+}
+
+fn set_webhook(handler: &InteractionHandler, guild_id: Snowflake, url: Option<String>) -> Result<String, String> {
+    let url = url.filter(|u| !u.is_empty());
+
+    if let Some(url) = &url {
+        if !WEBHOOK_URL_PREFIXES.iter().any(|prefix| url.starts_with(prefix)) {
+            return Err("Webhook URL must be a Discord webhook URL".to_string());
+        }
+        Webhook::new(url.clone()).map_err(|e| format!("Invalid webhook: {e}"))?;
+    }
+
+    store(handler.data.get::<UploadLedger>().unwrap().set_guild_webhook(guild_id, url.as_deref()))?;
+
+    Ok(match url {
+        Some(_) => "Uploads are now announced to the new webhook".to_string(),
+        None => "Removed the webhook set for this server".to_string(),
+    })
+}
+
+fn parse_extension(extension: Option<String>) -> Result<String, String> {
+    let extension = extension.ok_or("No extension provided")?
+        .trim_start_matches('.')
+        .to_ascii_lowercase();
+
+    if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("Extension may only contain letters and digits".to_string());
+    }
+
+    Ok(extension)
+}
+
+/// Guilds may only lower the size limit set in the bot configuration.
+fn check_global_limit(global: Option<usize>, max_size: Option<usize>) -> Result<(), String> {
+    match (global, max_size) {
+        (Some(global), Some(max_size)) if max_size > global => Err(format!("Maximum size must not exceed the limit of {} set by the bot configuration", human_bytes::human_bytes(global as f64))),
+        _ => Ok(()),
+    }
+}
+
+fn parse_max_size(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err("Maximum size must be a positive number of bytes".to_string()),
+    }
+}
+
+fn store(result: anyhow::Result<()>) -> Result<(), String> {
+    result.map_err(|e| {
+        log::error!("Failed to store guild settings: {e:#}");
+        "Failed to store the new settings".to_string()
+    })
+}
+
+/// Rebuilds the guild profiles from the loaded configuration and the stored guild settings.
+fn reload_profiles(handler: &InteractionHandler) -> anyhow::Result<()> {
+    let ledger = handler.data.get::<UploadLedger>().unwrap();
+    handler.data.get::<SharedConfig>().unwrap().update(|runtime| {
+        runtime.profiles = GuildProfiles::from_config(&runtime.config, &ledger.guild_settings()?)?;
+        Ok(())
+    })
+}
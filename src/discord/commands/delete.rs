@@ -0,0 +1,86 @@
+use rusty_interaction::{defer, slash_command};
+use rusty_interaction::handler::InteractionHandler;
+use rusty_interaction::types::interaction::{Context, InteractionResponse};
+
+use crate::discord::BotInfo;
+use crate::discord::runtime::SharedConfig;
+use crate::ledger::UploadLedger;
+use crate::upload::UploaderImpl;
+use crate::upload::thumbnail::thumbnail_path;
+
+#[defer]
+#[slash_command]
+pub(crate) async fn delete_command(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    let bot = handler.data.get::<BotInfo>().unwrap();
+    let runtime = handler.data.get::<SharedConfig>().unwrap().current();
+    let profile = runtime.profiles.get(ctx.interaction.guild_id);
+    let uploader = &runtime.uploader;
+    let ledger = handler.data.get::<UploadLedger>().unwrap();
+    let options = &runtime.options;
+
+    let user_id = match ctx.author_id {
+        Some(id) => id,
+        None => {
+            return ctx.respond()
+                .content("Cannot use this command without being a user")
+                .is_ephemeral(true)
+                .finish();
+        }
+    };
+
+    let data = &ctx.interaction.data.clone().unwrap();
+    let opts = data.options.clone().unwrap();
+    let file_name_option = opts.iter().find(|&o| o.name == "file-name").expect("No file name provided");
+    // stored paths are matched exactly, random names are case sensitive
+    let mut filename = file_name_option.value.trim().to_string();
+    if let Some(storage_prefix) = &profile.storage_prefix {
+        if !filename.starts_with(&format!("{storage_prefix}/")) {
+            filename = profile.storage_path(&filename);
+        }
+    }
+
+    let owned = match ledger.is_owned_by(&filename, user_id) {
+        Ok(owned) => owned,
+        Err(e) => {
+            log::error!("Failed to look up owner of {filename}: {e}");
+            return ctx.respond()
+                .content("Failed to check who uploaded this file")
+                .is_ephemeral(true)
+                .finish();
+        }
+    };
+    if user_id != bot.owner_id && !owned {
+        return ctx.respond()
+            .content("You can only delete your own uploads")
+            .is_ephemeral(true)
+            .finish();
+    }
+
+    match uploader.delete(&filename).await {
+        Ok(_) => {
+            log::info!("User {user_id} deleted file {filename}");
+            if let Err(e) = ledger.mark_deleted(&filename) {
+                log::error!("Failed to mark {filename} as deleted in ledger: {e}");
+            }
+
+            // thumbnails only exist for images, so failures here are expected
+            for width in &options.thumbnail_widths {
+                let path = thumbnail_path(&options.thumbnail_name_template, &filename, *width);
+                if let Err(e) = uploader.delete(&path).await {
+                    log::debug!("Did not delete thumbnail {path}: {e}");
+                }
+            }
+            ctx.respond()
+                .content(format!("Deleted `{filename}`"))
+                .is_ephemeral(true)
+                .finish()
+        }
+        Err(e) => {
+            log::error!("Failed to delete file {filename}: {e}");
+            ctx.respond()
+                .content(format!("Failed to delete file: {e}"))
+                .is_ephemeral(true)
+                .finish()
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use rusty_interaction::{Builder, component_handler, slash_command};
+use rusty_interaction::handler::InteractionHandler;
+use rusty_interaction::types::components::{ComponentButtonBuilder, ComponentButtonStyle, ComponentRowBuilder};
+use rusty_interaction::types::embed::{EmbedBuilder, EmbedField};
+use rusty_interaction::types::interaction::{Context, InteractionResponse, InteractionResponseType};
+use rusty_interaction::types::Snowflake;
+
+use crate::ledger::UploadLedger;
+
+const PAGE_ID_PREFIX: &str = "list-page:";
+
+/// The number of pages that can be reached through the buttons of a `/list` reply.
+pub(crate) const MAX_PAGES: usize = 200;
+
+const PAGE_SIZE: usize = 5;
+
+/// The custom id of buttons showing the given page, so each reply keeps track of its own page.
+pub(crate) fn page_id(page: usize) -> String {
+    format!("{PAGE_ID_PREFIX}{page}")
+}
+
+#[slash_command]
+pub(crate) async fn list_command(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    let user_id = match ctx.author_id {
+        Some(id) => id,
+        None => {
+            return ctx.respond()
+                .content("Cannot use this command without being a user")
+                .is_ephemeral(true)
+                .finish();
+        }
+    };
+
+    let ledger = handler.data.get::<UploadLedger>().unwrap();
+    list_response(&ctx, ledger, user_id, 0, false)
+}
+
+#[component_handler]
+pub(crate) async fn show_page(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    let user_id = match ctx.author_id {
+        Some(id) => id,
+        None => return ctx.respond().none(),
+    };
+
+    let page = ctx.interaction.data.as_ref()
+        .and_then(|data| data.custom_id.as_deref()?.strip_prefix(PAGE_ID_PREFIX)?.parse().ok())
+        .unwrap_or(0);
+
+    let ledger = handler.data.get::<UploadLedger>().unwrap();
+    list_response(&ctx, ledger, user_id, page, true)
+}
+
+/// Shows the given page of the user's uploads, or the last one if the page is beyond the end.
+fn list_response(ctx: &Context, ledger: &UploadLedger, user_id: Snowflake, page: usize, update: bool) -> InteractionResponse {
+    let total = match ledger.count_uploads(user_id) {
+        Ok(total) => total,
+        Err(e) => {
+            log::error!("Failed to count uploads of {user_id}: {e}");
+            return ctx.respond().is_ephemeral(true).content("Failed to look up your uploads").finish();
+        }
+    };
+
+    if total == 0 {
+        return ctx.respond().is_ephemeral(true).content("You have not uploaded any files yet").finish();
+    }
+
+    let page_count = total.div_ceil(PAGE_SIZE).min(MAX_PAGES);
+    let page = page.min(page_count - 1);
+
+    let records = match ledger.recent_uploads(user_id, PAGE_SIZE, page * PAGE_SIZE) {
+        Ok(records) => records,
+        Err(e) => {
+            log::error!("Failed to list uploads of {user_id}: {e}");
+            return ctx.respond().is_ephemeral(true).content("Failed to look up your uploads").finish();
+        }
+    };
+
+    let mut embed = EmbedBuilder::default()
+        .title(format!("Your uploads (page {}/{page_count})", page + 1));
+    for record in records {
+        embed = embed.add_field(EmbedField::default()
+            .name(record.path)
+            .value(format!("<{}>\n{} • <t:{}:f>", record.url, human_bytes::human_bytes(record.size as f64), record.uploaded_at.timestamp()))
+        );
+    }
+
+    let buttons = ComponentRowBuilder::default()
+        .add_button(ComponentButtonBuilder::default()
+            .label("Previous")
+            .custom_id(&page_id(page.saturating_sub(1)))
+            .style(&ComponentButtonStyle::Secondary)
+            .disabled(page == 0)
+            .build().unwrap()
+        )
+        .add_button(ComponentButtonBuilder::default()
+            .label("Next")
+            .custom_id(&page_id(page + 1))
+            .style(&ComponentButtonStyle::Secondary)
+            .disabled(page + 1 >= page_count)
+            .build().unwrap()
+        )
+        .build().unwrap();
+
+    let mut response = ctx.respond();
+    if update {
+        response = response.respond_type(InteractionResponseType::UpdateMessage);
+    }
+
+    response
+        .is_ephemeral(true)
+        .add_embed(embed.build().unwrap())
+        .add_component_row(buttons)
+        .finish()
+}
@@ -1,10 +1,27 @@
+mod config;
+mod delete;
+mod list;
+mod moderation;
 mod reload;
-mod upload;
+pub(crate) mod upload;
+pub(crate) mod upload_message;
 
 use rusty_interaction::handler::InteractionHandler;
 
 pub(crate) fn register_commands(handler: &mut InteractionHandler) {
+    handler.add_global_command("config", config::config_command);
+    handler.add_global_command("delete", delete::delete_command);
+    handler.add_global_command("list", list::list_command);
     handler.add_global_command("reload", reload::reload_commands);
     handler.add_global_command("upload", upload::upload_command);
+    handler.add_global_command(upload_message::COMMAND_NAME, upload_message::upload_message_command);
+
+    // component handles are matched by their exact custom id, so every page needs its own
+    for page in 0..list::MAX_PAGES {
+        handler.add_component_handle(Box::leak(list::page_id(page).into_boxed_str()), list::show_page);
+    }
+
+    handler.add_component_handle(moderation::APPROVE_ID, moderation::approve_upload);
+    handler.add_component_handle(moderation::REJECT_ID, moderation::reject_upload);
 }
 
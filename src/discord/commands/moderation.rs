@@ -0,0 +1,126 @@
+use rusty_interaction::{component_handler, defer};
+use rusty_interaction::handler::InteractionHandler;
+use rusty_interaction::types::interaction::{Context, InteractionResponse};
+
+use crate::discord;
+use crate::discord::BotInfo;
+use crate::discord::commands::upload::publish;
+pub(crate) use crate::discord::moderation::{APPROVE_ID, REJECT_ID};
+use crate::discord::runtime::SharedConfig;
+use crate::ledger::UploadLedger;
+
+#[defer]
+#[component_handler]
+pub(crate) async fn approve_upload(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    review(handler, ctx, true).await
+}
+
+#[defer]
+#[component_handler]
+pub(crate) async fn reject_upload(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    review(handler, ctx, false).await
+}
+
+async fn review(handler: &mut InteractionHandler, ctx: Context, approve: bool) -> InteractionResponse {
+    let bot = handler.data.get::<BotInfo>().unwrap();
+    let ledger = handler.data.get::<UploadLedger>().unwrap();
+    let runtime = handler.data.get::<SharedConfig>().unwrap().current();
+    let options = &runtime.options;
+
+    let queue = match &runtime.moderation {
+        Some(queue) => queue,
+        None => {
+            return ctx.respond()
+                .content("Upload moderation is disabled")
+                .is_ephemeral(true)
+                .finish();
+        }
+    };
+
+    let moderator_id = match ctx.author_id {
+        Some(id) => id,
+        None => return ctx.respond().none(),
+    };
+    let roles = ctx.interaction.member.as_ref().map(|m| m.roles.clone()).unwrap_or_default();
+    if moderator_id != bot.owner_id && !discord::can_manage_guild(&ctx) && !queue.is_moderator(&roles) {
+        return ctx.respond()
+            .content("Only moderators can review uploads")
+            .is_ephemeral(true)
+            .finish();
+    }
+
+    let message_id = match ctx.interaction.message.as_ref().map(|m| m.id) {
+        Some(id) => id,
+        None => return ctx.respond().none(),
+    };
+
+    let (id, upload) = match ledger.take_pending(message_id) {
+        Ok(Some(pending)) => pending,
+        Ok(None) => {
+            return ctx.respond()
+                .content("This upload was already reviewed")
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(e) => {
+            log::error!("Failed to look up pending upload for message {message_id}: {e:#}");
+            return ctx.respond()
+                .content("Failed to look up the upload")
+                .is_ephemeral(true)
+                .finish();
+        }
+    };
+    let path = upload.path.clone();
+
+    if !approve {
+        if let Err(e) = queue.staging.remove(id).await {
+            log::warn!("Failed to remove staged upload {id}: {e}");
+        }
+        log::info!("Moderator {moderator_id} rejected {path}");
+
+        return ctx.respond()
+            .content(format!("Rejected `{path}` by <@{}> (reviewed by <@{moderator_id}>)", upload.user_id))
+            .finish();
+    }
+
+    let image = if upload.content_type.starts_with("image/") && !options.thumbnail_widths.is_empty() {
+        match queue.staging.read(id).await {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                log::warn!("Failed to read staged upload {id} for thumbnails: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let result = match queue.staging.body(id).await {
+        Ok(body) => publish(handler, upload.clone(), body, image.as_deref()).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(published) => {
+            if let Err(e) = queue.staging.remove(id).await {
+                log::warn!("Failed to remove staged upload {id}: {e}");
+            }
+            log::info!("Moderator {moderator_id} approved {path}");
+
+            ctx.respond()
+                .content(format!("Approved <{}> by <@{}> (reviewed by <@{moderator_id}>)", published.url, upload.user_id))
+                .finish()
+        }
+        Err(e) => {
+            log::error!("Failed to publish approved upload {path}: {e:#}");
+            if let Err(e) = ledger.restore_pending(id, message_id, &upload) {
+                log::error!("Failed to restore pending upload {id}: {e:#}");
+            }
+
+            ctx.respond()
+                .content(format!("Failed to publish `{path}`: {e}"))
+                .is_ephemeral(true)
+                .finish()
+        }
+    }
+}
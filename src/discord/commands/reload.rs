@@ -1,6 +1,7 @@
 use rusty_interaction::{defer, slash_command};
 use rusty_interaction::handler::InteractionHandler;
 use rusty_interaction::types::interaction::{Context, InteractionResponse};
+use crate::discord;
 use crate::discord::BotInfo;
 use crate::discord::register::update_global_commands;
 
@@ -27,12 +28,26 @@ pub(crate) async fn reload_commands(handler: &mut InteractionHandler, ctx: Conte
         }
     }
 
+    log::info!("Reloading configuration");
+
+    let changes = match discord::reload(handler).await {
+        Ok(changes) => changes,
+        Err(e) => {
+            log::error!("Failed to reload configuration: {e:#}");
+            return ctx.respond()
+                .content(format!("Failed to reload configuration, keeping previous settings: {e}"))
+                .is_ephemeral(true)
+                .finish();
+        }
+    };
+
     log::info!("Reloading commands");
 
     match update_global_commands(handler, ctx.interaction.application_id.unwrap()).await {
         Ok(_) => {
+            let summary = changes.iter().map(|c| format!("- {c}")).collect::<Vec<String>>().join("\n");
             ctx.respond()
-                .content("Reloaded commands")
+                .content(format!("Reloaded commands and configuration:\n{summary}"))
                 .is_ephemeral(true)
                 .finish()
         }
@@ -1,118 +1,734 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Mutex;
+
 use chrono::Utc;
+use futures::{stream, StreamExt};
+use reqwest::Url;
 use rusty_interaction::{Builder, defer, slash_command};
 use rusty_interaction::handler::InteractionHandler;
 use rusty_interaction::types::embed::{EmbedBuilder, EmbedField};
 use rusty_interaction::types::interaction::{Context, InteractionResponse, WebhookMessage};
 use rusty_interaction::types::Snowflake;
 use crate::discord::BotInfo;
+use crate::discord::profile::GuildProfile;
+use crate::discord::runtime::SharedConfig;
+use crate::http;
+
+use crate::ledger::{PreparedUpload, UploadLedger};
+use crate::upload::{UploadBody, Uploader, UploaderImpl};
+use crate::upload::convert::{convert_to_webp, WEBP_CONTENT_TYPE};
+use crate::upload::metadata::strip_metadata;
+use crate::upload::naming::{self, NameNormalization, NameParts};
+use crate::upload::spool::SpooledFile;
+use crate::upload::thumbnail::{generate_thumbnails, thumbnail_path, THUMBNAIL_CONTENT_TYPE};
+use crate::util::{detect_extension, sha256_hex, UploadOptions};
 
-use crate::upload::{Uploader, UploaderImpl};
-use crate::util::UploadValidator;
+/// The attachment options of `/upload`, in the order their results are reported.
+pub(crate) const ATTACHMENT_OPTIONS: [&str; 10] = ["file", "file2", "file3", "file4", "file5", "file6", "file7", "file8", "file9", "file10"];
+const MAX_CONCURRENT_UPLOADS: usize = 3;
 
 const DISALLOWED_CHARACTERS: [char; 31] = ['(', ')', '[', ']', '{', '}', '-', '+', '*', '=', '&', '@', '!', '?', '\'', '#', '$', '%', '^', '~', '^', '´', '`', ':', ',', ';', '<', '>', '|', '\"', '\\'];
 
 #[defer]
 #[slash_command]
 pub(crate) async fn upload_command(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
-    let bot = handler.data.get::<BotInfo>().unwrap();
-    let validator = handler.data.get::<UploadValidator>().unwrap();
-    let uploader = handler.data.get::<Uploader>().unwrap();
-
+    let author = UploadAuthor::from_context(&ctx);
+    if let Err(message) = check_access(handler, &author) {
+        return ctx.respond().is_ephemeral(true).content(message).finish();
+    }
 
     let data = &ctx.interaction.data.clone().unwrap();
-    let opts = data.options.clone().unwrap();
-    let attachment_option = opts.iter().find(|&o| o.name == "file").expect("No attachment provided");
+    let opts = data.options.clone().unwrap_or_default();
     let file_name_option = opts.iter().find(|&o| o.name == "file-name");
+    let url_option = opts.iter().find(|&o| o.name == "url");
+    let on_conflict = match opts.iter().find(|&o| o.name == "on-conflict").map(|o| o.value.parse::<ConflictMode>()).transpose() {
+        Ok(mode) => mode.unwrap_or(ConflictMode::Fail),
+        Err(message) => return ctx.respond().is_ephemeral(true).content(message).finish(),
+    };
+    let attachments = data.resolved.clone().and_then(|r| r.attachments).unwrap_or_default();
 
-    let attachment_id: Snowflake = attachment_option.value.parse().expect("Invalid attachment id");
-    let attachments = data.resolved.clone().unwrap().attachments.unwrap();
-    let attachment = attachments.get(&attachment_id).expect("Attachment not found");
+    let mut sources = Vec::with_capacity(ATTACHMENT_OPTIONS.len());
+    for name in ATTACHMENT_OPTIONS {
+        if let Some(option) = opts.iter().find(|&o| o.name == name) {
+            let attachment_id: Snowflake = option.value.parse().expect("Invalid attachment id");
+            let attachment = attachments.get(&attachment_id).expect("Attachment not found");
 
-    let desired_file_name = file_name_option.map(|o| o.value.clone()).unwrap_or_else(|| attachment.filename.clone());
+            sources.push(UploadSource {
+                url: attachment.url.clone(),
+                file_name: attachment.filename.clone(),
+                size: Some(attachment.size),
+                content_type: attachment.content_type.clone(),
+                remote: false,
+            });
+        }
+    }
+    if let Some(option) = url_option {
+        let url = option.value.trim().to_string();
+        sources.push(UploadSource {
+            file_name: remote_file_name(&url),
+            url,
+            size: None,
+            content_type: None,
+            remote: true,
+        });
+    }
 
-    let user_id = &ctx.interaction.member.clone().map(|m| m.user.id).unwrap_or(0);
-    let user_id_string = format!("{:0width$}", user_id, width = 20);
-    let prefix = &user_id_string[16..];
-    assert_eq!(prefix.len(), 4, "Prefix must be 4 characters long");
+    if sources.is_empty() {
+        return ctx.respond().is_ephemeral(true).content("Attach a file or give a URL to upload").finish();
+    }
+    if file_name_option.is_some() && sources.len() > 1 {
+        return ctx.respond().is_ephemeral(true).content("`file-name` can only be used when uploading a single file").finish();
+    }
 
-    let filename = format!("{prefix}_{desired_file_name}").to_ascii_lowercase();
+    // a single file keeps the detailed reply and may be renamed
+    if let [source] = sources.as_slice() {
+        let file_name = file_name_option.map(|option| option.value.as_str());
+        return match upload_file(handler, &author, source, file_name, on_conflict, &PathClaims::default()).await {
+            Ok(outcome @ UploadOutcome::Queued(_)) => ctx.respond().is_ephemeral(true).content(outcome.describe()).finish(),
+            Ok(outcome) => ctx.respond().content(outcome.describe()).finish(),
+            Err(message) => ctx.respond().is_ephemeral(true).content(message).finish(),
+        };
+    }
 
-    if !filename.is_ascii() {
-        return ctx.respond().is_ephemeral(true).content("File name must be valid ASCII").finish();
+    match upload_files(handler, &author, sources, on_conflict).await {
+        Ok(reply) => ctx.respond().is_ephemeral(!reply.any_public).content(reply.content).finish(),
+        Err(message) => ctx.respond().is_ephemeral(true).content(message).finish(),
     }
+}
+
+/// Combined reply for several uploaded files.
+pub(crate) struct BatchReply {
+    pub(crate) content: String,
+    /// Whether any file is publicly available, otherwise the reply should only be shown to the user.
+    pub(crate) any_public: bool,
+}
+
+/// Uploads several files at once, with at most [`MAX_CONCURRENT_UPLOADS`] in flight, reporting the result of each one.
+///
+/// The quota is checked for the whole batch first, so parallel uploads cannot exceed it together.
+pub(crate) async fn upload_files(handler: &InteractionHandler, author: &UploadAuthor, sources: Vec<UploadSource>, on_conflict: ConflictMode) -> Result<BatchReply, String> {
+    let bot = handler.data.get::<BotInfo>().unwrap();
+    let ledger = handler.data.get::<UploadLedger>().unwrap();
+    let runtime = handler.data.get::<SharedConfig>().unwrap().current();
+    let quotas = &runtime.quotas;
 
-    if filename.chars().any(|c| DISALLOWED_CHARACTERS.contains(&c)) {
-        return ctx.respond().is_ephemeral(true).content(format!("File name must not contain any of the following characters: {}", DISALLOWED_CHARACTERS.iter().collect::<String>())).finish();
+    if author.user_id != bot.owner_id {
+        let total_size = sources.iter().map(|source| source.size.unwrap_or(0) as u64).sum();
+        quotas.check(ledger, author.user_id, &author.roles, sources.len(), total_size).map_err(|message| {
+            log::info!("User {} exceeded their upload quota", author.user_id);
+            message
+        })?;
     }
 
-    let frontend_url = uploader.frontend_url(&filename);
-    if let Err(message) = validator.check(&frontend_url, &attachment.filename.to_ascii_lowercase(), attachment.size) {
-        return ctx.respond().is_ephemeral(true).content(message).finish();
+    let claims = &PathClaims::default();
+    let results: Vec<(String, Result<UploadOutcome, String>)> = stream::iter(sources)
+        .map(|source| async move {
+            let result = upload_file(handler, author, &source, None, on_conflict, claims).await;
+            (source.file_name, result)
+        })
+        .buffered(MAX_CONCURRENT_UPLOADS)
+        .collect()
+        .await;
+
+    let mut lines = Vec::with_capacity(results.len());
+    let mut any_public = false;
+    for (file_name, result) in results {
+        match result {
+            Ok(outcome) => {
+                any_public |= !matches!(outcome, UploadOutcome::Queued(_));
+                lines.push(format!("`{file_name}`: {}", outcome.summary()));
+            }
+            Err(message) => lines.push(format!("`{file_name}`: {message}")),
+        }
+    }
+
+    Ok(BatchReply {
+        content: lines.join("\n"),
+        any_public,
+    })
+}
+
+/// The user uploading files and where they do it.
+pub(crate) struct UploadAuthor {
+    pub(crate) user_id: Snowflake,
+    pub(crate) guild_id: Option<Snowflake>,
+    pub(crate) roles: Vec<Snowflake>,
+}
+
+impl UploadAuthor {
+    pub(crate) fn from_context(ctx: &Context) -> Self {
+        UploadAuthor {
+            user_id: ctx.author_id.unwrap_or(0),
+            guild_id: ctx.interaction.guild_id,
+            roles: ctx.interaction.member.as_ref().map(|m| m.roles.clone()).unwrap_or_default(),
+        }
     }
+}
+
+/// A file to download and upload, a Discord attachment or a remote file given by URL.
+pub(crate) struct UploadSource {
+    pub(crate) url: String,
+    pub(crate) file_name: String,
+    /// Only known in advance for attachments.
+    pub(crate) size: Option<usize>,
+    pub(crate) content_type: Option<String>,
+    /// Remote files are fetched with the size cap and address checks of [`http::fetch_remote`].
+    pub(crate) remote: bool,
+}
+
+/// The last path segment of a URL, used as the name of the remote file.
+fn remote_file_name(url: &str) -> String {
+    Url::parse(url).ok()
+        .and_then(|url| url.path_segments()?.last().map(|segment| segment.to_string()))
+        .filter(|segment| !segment.is_empty())
+        .unwrap_or("download".to_string())
+}
+
+/// Whether the file name ends with an extension, e.g. not for names taken from URLs like `/image?id=3`.
+fn has_extension(file_name: &str) -> bool {
+    file_name.rsplit_once('.').is_some_and(|(stem, ext)| !stem.is_empty() && !ext.is_empty())
+}
+
+/// Fetches a remote file with the size cap of the config, returning it with the `Content-Type` of the response.
+async fn fetch_remote_file(url: &str, options: &UploadOptions) -> Result<(SpooledFile, Option<String>), String> {
+    let response = http::fetch_remote(url, options.remote_timeout).await
+        .map_err(|e| format!("Failed to fetch URL: {e}"))?;
+    let content_type = response.headers().get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    let spooled = SpooledFile::download(response, Some(options.remote_max_size)).await
+        .map_err(|e| format!("Failed to fetch URL: {e}"))?;
 
-    let bytes: Vec<u8>;
-    match handler.client().clone().get(attachment.url.clone()).send().await {
-        Ok(response) => {
-            if !response.status().is_success() {
-                return ctx.respond().is_ephemeral(true).content("Failed to download attachment").finish();
+    Ok((spooled, content_type))
+}
+
+/// What happened to a single file.
+pub(crate) enum UploadOutcome {
+    Uploaded {
+        published: Published,
+        metadata_removed: bool,
+        /// The file name after normalization, if it differs from the one given.
+        normalized_name: Option<String>,
+    },
+    Duplicate(String),
+    Queued(String),
+}
+
+impl UploadOutcome {
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            UploadOutcome::Uploaded { published, metadata_removed, normalized_name } => {
+                let mut reply = format!("successfully uploaded as <{}>", published.url);
+                if let Some(name) = normalized_name {
+                    reply = format!("{reply}\nfile name normalized to `{name}`");
+                }
+                if *metadata_removed {
+                    reply = format!("{reply}\nremoved image metadata (EXIF/XMP/GPS)");
+                }
+                if !published.thumbnail_urls.is_empty() {
+                    reply = format!("{reply}\nthumbnails:\n{}", published.thumbnail_list());
+                }
+                reply
             }
-            match response.bytes().await {
-                Ok(b) => {
-                    bytes = b.to_vec();
-                },
-                Err(e) => {
-                    return ctx.respond().is_ephemeral(true).content(format!("Failed to download attachment: {e}")).finish();
+            UploadOutcome::Duplicate(existing) => format!("This file was already uploaded as <{existing}>"),
+            UploadOutcome::Queued(url) => format!("Your upload is waiting for review and will be available at <{url}> once a moderator approves it"),
+        }
+    }
+
+    /// A single line for replies that list several files.
+    pub(crate) fn summary(&self) -> String {
+        match self {
+            UploadOutcome::Uploaded { published, metadata_removed: true, .. } => format!("<{}> (removed image metadata)", published.url),
+            UploadOutcome::Uploaded { published, .. } => format!("<{}>", published.url),
+            UploadOutcome::Duplicate(existing) => format!("<{existing}> (already uploaded)"),
+            UploadOutcome::Queued(url) => format!("<{url}> (waiting for review)"),
+        }
+    }
+}
+
+/// What to do when the target path of an upload is already taken.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum ConflictMode {
+    Fail,
+    /// Appends `-1`, `-2`, ... to the file name until a free one is found.
+    Suffix,
+    /// Replaces the existing file, only if it belongs to the same user.
+    Overwrite,
+}
+
+impl FromStr for ConflictMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(ConflictMode::Fail),
+            "suffix" => Ok(ConflictMode::Suffix),
+            "overwrite" => Ok(ConflictMode::Overwrite),
+            _ => Err(format!("Unknown conflict mode `{s}`")),
+        }
+    }
+}
+
+/// The paths picked by the files of one upload, so files with the same name cannot pick the same target while they are processed concurrently.
+#[derive(Debug, Default)]
+pub(crate) struct PathClaims(Mutex<HashSet<String>>);
+
+impl PathClaims {
+    /// Reserves the path, returning false if another file of the upload already did.
+    fn claim(&self, path: &str) -> bool {
+        self.0.lock().expect("Path claims lock poisoned").insert(path.to_string())
+    }
+}
+
+const MAX_SUFFIX: u32 = 100;
+
+/// Picks the path to store the file at, returning whether the existing file should be overwritten.
+async fn resolve_conflict(uploader: &Uploader, ledger: &UploadLedger, claims: &PathClaims, path: String, mode: ConflictMode, user_id: Snowflake) -> Result<(String, bool), String> {
+    let claimed = claims.claim(&path);
+    if claimed && !path_taken(uploader, ledger, &path).await? {
+        return Ok((path, false));
+    }
+
+    match mode {
+        ConflictMode::Fail | ConflictMode::Overwrite if !claimed => Err(format!("Another file of this upload is already stored as `{path}`")),
+        ConflictMode::Fail => Err(format!("A file named `{path}` already exists, choose another name or use the `on-conflict` option")),
+        ConflictMode::Overwrite if is_pending(ledger, &path)? => Err(format!("An upload to `{path}` is waiting for review, it cannot be overwritten yet")),
+        ConflictMode::Overwrite => match ledger.is_owned_by(&path, user_id) {
+            Ok(true) => Ok((path, true)),
+            Ok(false) => Err(format!("`{path}` belongs to another user and cannot be overwritten")),
+            Err(e) => {
+                log::error!("Failed to look up owner of {path}: {e:#}");
+                Err("Failed to check who uploaded the existing file".to_string())
+            }
+        },
+        ConflictMode::Suffix => {
+            for n in 1..=MAX_SUFFIX {
+                let candidate = naming::with_suffix(&path, n);
+                if claims.claim(&candidate) && !path_taken(uploader, ledger, &candidate).await? {
+                    return Ok((candidate, false));
                 }
-            };
+            }
+            Err(format!("No free name found for `{path}`"))
         }
-        Err(e) => {
-            return ctx.respond().is_ephemeral(true).content(format!("Failed to download attachment: {e}")).finish();
-        }
-    }
-
-    let content_type = attachment.content_type.clone().unwrap_or("application/octet-stream".to_string());
-    match uploader.upload(&filename, bytes, &content_type).await {
-        Ok(result) => {
-            log::info!("Successfully uploaded file at {result}");
-
-            if let Some(webhooks) = &bot.webhooks {
-
-                let message = WebhookMessage {
-                    username: Some("PictureBot".to_string()),
-                    avatar_url: bot.webhook_logo_url.clone(),
-                    embeds: Some(vec![EmbedBuilder::default()
-                        .title("New upload")
-                        .add_field(EmbedField::default()
-                            .name("Discord User")
-                            .value(format!("`{user_id}` <@{user_id}>"))
-                        )
-                        .add_field(EmbedField::default()
-                            .name("URL")
-                            .value(format!("<{result}>"))
-                        )
-                        .timestamp(Utc::now())
-                        .build().unwrap()
-                    ]),
-                    ..Default::default()
-                };
-
-                for webhook in webhooks {
-                    match webhook.send(&message).await {
-                        Ok(_) => {
-                            log::debug!("Successfully dispatched webhook request to {}", webhook.url);
-                        },
-                        Err(e) => {
-                            log::error!("Failed to send webhook request to {}: {}", webhook.url, e);
-                        }
-                    }
+    }
+}
+
+const MAX_RANDOM_NAME_ATTEMPTS: u32 = 10;
+
+/// Renders the template with new random ids until the path is not taken.
+async fn free_random_path(uploader: &Uploader, ledger: &UploadLedger, claims: &PathClaims, profile: &GuildProfile, template: &str, parts: &mut NameParts<'_>) -> Result<String, String> {
+    for attempt in 0..MAX_RANDOM_NAME_ATTEMPTS {
+        if attempt > 0 {
+            parts.random = naming::random_id();
+        }
+        let path = profile.storage_path(&naming::render(template, parts)?);
+        if claims.claim(&path) && !path_taken(uploader, ledger, &path).await? {
+            return Ok(path);
+        }
+        log::debug!("Random path {path} is taken, trying another one");
+    }
+
+    Err("No free file name found, please try again".to_string())
+}
+
+/// Whether a file is stored at the path, or an upload waiting for moderation will be.
+async fn path_taken(uploader: &Uploader, ledger: &UploadLedger, path: &str) -> Result<bool, String> {
+    if is_pending(ledger, path)? {
+        return Ok(true);
+    }
+
+    uploader.exists(path).await.map_err(|e| {
+        log::error!("Failed to check whether {path} exists: {e}");
+        "Failed to check whether the file already exists".to_string()
+    })
+}
+
+fn is_pending(ledger: &UploadLedger, path: &str) -> Result<bool, String> {
+    ledger.is_pending(path).map_err(|e| {
+        log::error!("Failed to check whether {path} is pending: {e:#}");
+        "Failed to check whether the file already exists".to_string()
+    })
+}
+
+/// Checks the access lists of the guild, the bot owner may always upload.
+pub(crate) fn check_access(handler: &InteractionHandler, author: &UploadAuthor) -> Result<(), String> {
+    let bot = handler.data.get::<BotInfo>().unwrap();
+    let runtime = handler.data.get::<SharedConfig>().unwrap().current();
+    let profile = runtime.profiles.get(author.guild_id);
+
+    if author.user_id == bot.owner_id {
+        return Ok(());
+    }
+
+    profile.access.check(author.user_id, &author.roles).map_err(|message| {
+        log::info!("Denied upload for user {} in guild {:?}", author.user_id, author.guild_id);
+        message
+    })
+}
+
+/// Validates, downloads, processes and uploads a single file, returning a message for the user if anything fails.
+///
+/// The file is stored under `file_name` if given, otherwise under the name of the source.
+pub(crate) async fn upload_file(handler: &InteractionHandler, author: &UploadAuthor, source: &UploadSource, file_name: Option<&str>, on_conflict: ConflictMode, claims: &PathClaims) -> Result<UploadOutcome, String> {
+    let bot = handler.data.get::<BotInfo>().unwrap();
+    // one snapshot for the whole upload, so a reload cannot mix old and new settings
+    let runtime = handler.data.get::<SharedConfig>().unwrap().current();
+    let profile = runtime.profiles.get(author.guild_id);
+    let validator = &profile.validator;
+    let options = &runtime.options;
+    let uploader = &runtime.uploader;
+    let ledger = handler.data.get::<UploadLedger>().unwrap();
+    let quotas = &runtime.quotas;
+
+    let user_id = author.user_id;
+    if user_id != bot.owner_id {
+        quotas.check(ledger, user_id, &author.roles, 1, source.size.unwrap_or(0) as u64).map_err(|message| {
+            log::info!("User {user_id} exceeded their upload quota");
+            message
+        })?;
+    }
+
+    // remote files are fetched first, as the type of files from URLs without extension is only known from their content
+    let remote = if source.remote {
+        Some(fetch_remote_file(&source.url, options).await?)
+    } else {
+        None
+    };
+    let source_file_name = match &remote {
+        Some((spooled, content_type)) if !has_extension(&source.file_name) => {
+            let ext = detect_extension(&spooled.head, content_type.as_deref())
+                .ok_or("Unrecognized file type, give a URL ending in a file extension")?;
+            format!("{}.{ext}", source.file_name)
+        }
+        _ => source.file_name.clone(),
+    };
+
+    let desired_file_name = file_name.unwrap_or(&source_file_name);
+    let normalized_file_name = match options.name_normalization {
+        NameNormalization::Reject => desired_file_name.to_ascii_lowercase(),
+        NameNormalization::Slugify => naming::slugify(desired_file_name),
+    };
+    let keeps_name = naming::uses_name(&options.name_template);
+    let renamed = keeps_name && normalized_file_name != desired_file_name.to_ascii_lowercase();
+    let converted_file_name = validator.converted_path(&normalized_file_name);
+    let (name, ext) = converted_file_name.rsplit_once('.').unwrap_or((&converted_file_name, ""));
+
+    // names that are not part of the path only need a usable extension
+    if keeps_name {
+        if !converted_file_name.is_ascii() {
+            return Err("File name must be valid ASCII".to_string());
+        }
+
+        if converted_file_name.chars().any(|c| DISALLOWED_CHARACTERS.contains(&c)) {
+            return Err(format!("File name must not contain any of the following characters: {}", DISALLOWED_CHARACTERS.iter().collect::<String>()));
+        }
+    } else if !ext.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("File extension must only contain letters and digits".to_string());
+    }
+
+    // only names chosen by the user are sent along, so random names do not reveal the original file name
+    let download_name = file_name.filter(|_| !keeps_name).map(|file_name| match file_name.rsplit_once('.') {
+        Some((stem, _)) => format!("{stem}.{ext}"),
+        None => file_name.to_string(),
+    });
+
+    let uploaded_at = Utc::now();
+    let mut name_parts = NameParts {
+        user_id,
+        guild_id: author.guild_id,
+        date: uploaded_at,
+        hash: None,
+        random: naming::random_id(),
+        name,
+        ext,
+    };
+    // templates using the hash are rendered again once the content is known
+    let filename = profile.storage_path(&naming::render(&options.name_template, &name_parts)?);
+
+    let source_file_name = source_file_name.to_ascii_lowercase();
+    validator.check(&uploader.frontend_url(&filename), &source_file_name, source.size.unwrap_or(0))?;
+
+    let (spooled, response_content_type) = match remote {
+        Some(fetched) => fetched,
+        None => match handler.client().clone().get(source.url.clone()).send().await {
+            Ok(response) => {
+                if !response.status().is_success() {
+                    return Err("Failed to download attachment".to_string());
                 }
+                let spooled = SpooledFile::download(response, None).await
+                    .map_err(|e| format!("Failed to download attachment: {e}"))?;
+                (spooled, None)
             }
+            Err(e) => {
+                return Err(format!("Failed to download attachment: {e}"));
+            }
+        },
+    };
 
-            ctx.respond().content(format!("successfully uploaded as <{result}>")).finish()
+    // the size of remote files is only known now
+    if source.size.is_none() {
+        validator.check(&uploader.frontend_url(&filename), &source_file_name, spooled.size as usize)?;
+        if user_id != bot.owner_id {
+            quotas.check(ledger, user_id, &author.roles, 1, spooled.size)?;
         }
+    }
+
+    let detected_content_type = validator.check_content(&source_file_name, &spooled.head)?;
+
+    let content_type = detected_content_type.map(|s| s.to_string())
+        .or_else(|| source.content_type.clone())
+        .or(response_content_type)
+        .unwrap_or("application/octet-stream".to_string());
+
+    let source_extension = source_file_name.rsplit('.').next().unwrap_or_default();
+    let conversion = validator.conversion(source_extension);
+    let is_image = content_type.starts_with("image/");
+
+    // only images that need processing are loaded into memory, everything else is streamed from disk
+    let mut metadata_removed = false;
+    let (processed, content_type) = if conversion.is_some() || (is_image && (options.strip_metadata || !options.thumbnail_widths.is_empty())) {
+        let bytes = spooled.read().await.map_err(|e| {
+            log::error!("Failed to read downloaded attachment: {e}");
+            "Failed to read attachment".to_string()
+        })?;
+
+        let (bytes, content_type) = match conversion {
+            Some("webp") => match convert_to_webp(bytes, options.webp_quality).await {
+                Ok(converted) => (converted, WEBP_CONTENT_TYPE.to_string()),
+                Err(e) => {
+                    log::error!("Failed to convert {filename} to WebP: {e}");
+                    return Err("Failed to convert image".to_string());
+                }
+            },
+            _ => (bytes, content_type),
+        };
+
+        let bytes = if options.strip_metadata {
+            match strip_metadata(bytes, &content_type) {
+                Ok((stripped, removed)) => {
+                    metadata_removed = removed;
+                    stripped
+                }
+                Err(e) => {
+                    log::error!("Failed to strip metadata from {filename}: {e}");
+                    return Err("Failed to remove image metadata".to_string());
+                }
+            }
+        } else {
+            bytes
+        };
+
+        (Some(bytes), content_type)
+    } else {
+        (None, content_type)
+    };
+
+    let (size, hash) = match &processed {
+        Some(bytes) => (bytes.len(), sha256_hex(bytes)),
+        None => (spooled.size as usize, spooled.hash.clone()),
+    };
+    if options.deduplicate {
+        match ledger.find_by_hash(&hash, author.guild_id, profile.storage_prefix.as_deref()) {
+            Ok(Some(existing)) => match uploader.exists(&existing.path).await {
+                Ok(true) => {
+                    log::info!("Skipping upload of {filename}, identical file exists at {}", existing.url);
+                    return Ok(UploadOutcome::Duplicate(existing.url));
+                }
+                Ok(false) => log::warn!("Recorded upload {} no longer exists", existing.path),
+                Err(e) => log::warn!("Failed to check whether {} exists: {e}", existing.path),
+            },
+            Ok(None) => {}
+            Err(e) => {
+                log::warn!("Failed to look up duplicate uploads: {e:#}");
+            }
+        }
+    }
+
+    let filename = if naming::uses_hash(&options.name_template) {
+        name_parts.hash = Some(&hash);
+        profile.storage_path(&naming::render(&options.name_template, &name_parts)?)
+    } else {
+        filename
+    };
+    let (filename, overwrite) = if naming::uses_random(&options.name_template) {
+        (free_random_path(uploader, ledger, claims, profile, &options.name_template, &mut name_parts).await?, false)
+    } else {
+        resolve_conflict(uploader, ledger, claims, filename, on_conflict, user_id).await?
+    };
+    let frontend_url = uploader.frontend_url(&filename);
+
+    let upload = PreparedUpload {
+        user_id,
+        guild_id: author.guild_id,
+        original_name: source.file_name.clone(),
+        path: filename.clone(),
+        content_type,
+        size,
+        hash,
+        uploaded_at,
+        overwrite,
+        download_name,
+    };
+
+    let image = processed.as_deref().filter(|_| upload.content_type.starts_with("image/"));
+    let body = match &processed {
+        Some(bytes) => UploadBody::from_bytes(bytes.clone()),
+        None => spooled.body().await.map_err(|e| {
+            log::error!("Failed to read downloaded attachment: {e}");
+            "Failed to read attachment".to_string()
+        })?,
+    };
+
+    if let Some(queue) = &runtime.moderation {
+        return match queue.submit(ledger, &upload, body, &source.url, runtime.webhook_logo_url.clone()).await {
+            Ok(_) => {
+                log::info!("Queued {filename} for moderation");
+                Ok(UploadOutcome::Queued(frontend_url))
+            }
+            Err(e) => {
+                log::error!("Failed to queue {filename} for moderation: {e:#}");
+                Err("Failed to submit upload for review".to_string())
+            }
+        };
+    }
+
+    match publish(handler, upload, body, image).await {
+        Ok(published) => Ok(UploadOutcome::Uploaded {
+            published,
+            metadata_removed,
+            normalized_name: renamed.then_some(normalized_file_name),
+        }),
         Err(e) => {
             log::error!("Failed to upload file: {e}");
-            ctx.respond().is_ephemeral(true).content(format!("Failed to upload file: {e}")).finish()
+            Err(format!("Failed to upload file: {e}"))
         }
     }
-}
\ No newline at end of file
+}
+
+pub(crate) struct Published {
+    pub(crate) url: String,
+    pub(crate) thumbnail_urls: Vec<String>,
+}
+
+impl Published {
+    pub(crate) fn thumbnail_list(&self) -> String {
+        self.thumbnail_urls.iter().map(|url| format!("<{url}>")).collect::<Vec<String>>().join("\n")
+    }
+}
+
+/// Stores a checked upload with its thumbnails, records it and announces it to the webhooks of its guild.
+///
+/// Thumbnails are only generated if the decoded image is passed along.
+pub(crate) async fn publish(handler: &InteractionHandler, upload: PreparedUpload, body: UploadBody, image: Option<&[u8]>) -> anyhow::Result<Published> {
+    let runtime = handler.data.get::<SharedConfig>().unwrap().current();
+    let profile = runtime.profiles.get(upload.guild_id);
+    let options = &runtime.options;
+    let uploader = &runtime.uploader;
+    let ledger = handler.data.get::<UploadLedger>().unwrap();
+    let filename = upload.path.clone();
+    let user_id = upload.user_id;
+
+    let mut thumbnails = Vec::with_capacity(0);
+    if let Some(bytes) = image {
+        if !options.thumbnail_widths.is_empty() {
+            match generate_thumbnails(bytes, &options.thumbnail_widths).await {
+                Ok(t) => thumbnails = t,
+                Err(e) => log::warn!("Failed to generate thumbnails for {filename}: {e}"),
+            }
+        }
+    }
+
+    let download_name = upload.download_name.as_deref();
+    let result = if upload.overwrite {
+        uploader.overwrite(&filename, body, &upload.content_type, download_name).await?
+    } else {
+        uploader.upload(&filename, body, &upload.content_type, download_name).await?
+    };
+    log::info!("Successfully uploaded file at {result}");
+
+    let mut thumbnail_urls = Vec::with_capacity(thumbnails.len());
+    let mut thumbnail_widths = Vec::with_capacity(thumbnails.len());
+    for thumbnail in thumbnails {
+        let path = thumbnail_path(&options.thumbnail_name_template, &filename, thumbnail.width);
+        let thumbnail_body = UploadBody::from_bytes(thumbnail.bytes);
+        let stored = if upload.overwrite {
+            uploader.overwrite(&path, thumbnail_body, THUMBNAIL_CONTENT_TYPE, None).await
+        } else {
+            uploader.upload(&path, thumbnail_body, THUMBNAIL_CONTENT_TYPE, None).await
+        };
+        match stored {
+            Ok(url) => {
+                thumbnail_urls.push(url);
+                thumbnail_widths.push(thumbnail.width);
+            }
+            Err(e) => log::error!("Failed to upload thumbnail {path}: {e}"),
+        }
+    }
+
+    if upload.overwrite {
+        // thumbnails of the replaced file would show its old content
+        for width in options.thumbnail_widths.iter().filter(|width| !thumbnail_widths.contains(width)) {
+            let path = thumbnail_path(&options.thumbnail_name_template, &filename, *width);
+            if let Err(e) = uploader.delete(&path).await {
+                log::debug!("Did not delete outdated thumbnail {path}: {e}");
+            }
+        }
+        // also drops the old content from duplicate lookups, which only consider uploads that are not deleted
+        if let Err(e) = ledger.mark_deleted(&filename) {
+            log::error!("Failed to mark overwritten {filename} as deleted in ledger: {e}");
+        }
+    }
+    let record = upload.into_record(result.clone());
+    if let Err(e) = ledger.record(&record) {
+        log::error!("Failed to record upload of {filename} in ledger: {e}");
+    }
+
+    let published = Published {
+        url: result,
+        thumbnail_urls,
+    };
+
+    if let Some(webhooks) = &profile.webhooks {
+
+        let mut embed = EmbedBuilder::default()
+            .title("New upload")
+            .add_field(EmbedField::default()
+                .name("Discord User")
+                .value(format!("`{user_id}` <@{user_id}>"))
+            )
+            .add_field(EmbedField::default()
+                .name("URL")
+                .value(format!("<{}>", published.url))
+            );
+        if !published.thumbnail_urls.is_empty() {
+            embed = embed.add_field(EmbedField::default()
+                .name("Thumbnails")
+                .value(published.thumbnail_list())
+            );
+        }
+
+        let message = WebhookMessage {
+            username: Some("PictureBot".to_string()),
+            avatar_url: runtime.webhook_logo_url.clone(),
+            embeds: Some(vec![embed
+                .timestamp(Utc::now())
+                .build().unwrap()
+            ]),
+            ..Default::default()
+        };
+
+        for webhook in webhooks {
+            match webhook.send(&message).await {
+                Ok(_) => {
+                    log::debug!("Successfully dispatched webhook request to {}", webhook.url);
+                },
+                Err(e) => {
+                    log::error!("Failed to send webhook request to {}: {}", webhook.url, e);
+                }
+            }
+        }
+    }
+
+    Ok(published)
+}
@@ -0,0 +1,44 @@
+use rusty_interaction::{defer, slash_command};
+use rusty_interaction::handler::InteractionHandler;
+use rusty_interaction::types::interaction::{Context, InteractionResponse};
+
+use crate::discord::commands::upload::{check_access, upload_files, ConflictMode, UploadAuthor, UploadSource};
+
+pub(crate) const COMMAND_NAME: &str = "Upload to CDN";
+
+/// Message context menu command uploading every attachment of the selected message.
+#[defer]
+#[slash_command]
+pub(crate) async fn upload_message_command(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    let author = UploadAuthor::from_context(&ctx);
+    if let Err(message) = check_access(handler, &author) {
+        return ctx.respond().is_ephemeral(true).content(message).finish();
+    }
+
+    let data = ctx.interaction.data.clone().unwrap();
+    let message = data.target_id
+        .and_then(|id| data.resolved.as_ref()?.messages.as_ref()?.get(&id).cloned());
+    let message = match message {
+        Some(message) => message,
+        None => return ctx.respond().is_ephemeral(true).content("Message not found").finish(),
+    };
+
+    if message.attachments.is_empty() {
+        return ctx.respond().is_ephemeral(true).content("This message has no attachments").finish();
+    }
+
+    let sources = message.attachments.iter()
+        .map(|attachment| UploadSource {
+            url: attachment.url.clone(),
+            file_name: attachment.filename.clone(),
+            size: Some(attachment.size),
+            content_type: attachment.content_type.clone(),
+            remote: false,
+        })
+        .collect();
+
+    match upload_files(handler, &author, sources, ConflictMode::Fail).await {
+        Ok(reply) => ctx.respond().is_ephemeral(!reply.any_public).content(reply.content).finish(),
+        Err(message) => ctx.respond().is_ephemeral(true).content(message).finish(),
+    }
+}
@@ -1,42 +1,121 @@
 use anyhow::Context;
 use rusty_interaction::handler::InteractionHandler;
+use rusty_interaction::types::interaction::Context as InteractionContext;
 use rusty_interaction::types::Snowflake;
 
+use crate::config::Config;
+use crate::discord::runtime::{RuntimeConfig, SharedConfig};
 use crate::discord::webhook::Webhook;
+use crate::ledger::UploadLedger;
 
 mod commands;
+pub(crate) mod moderation;
+pub(crate) mod profile;
+pub(crate) mod quota;
 pub(crate) mod register;
+pub(crate) mod runtime;
 mod webhook;
 
 #[derive(Debug, Clone)]
 pub struct BotInfo {
     pub app_id: Snowflake,
     pub owner_id: Snowflake,
-    pub webhooks: Option<Vec<Webhook>>,
-    pub webhook_logo_url: Option<String>,
 }
 
-pub(crate) async fn init() -> anyhow::Result<InteractionHandler> {
+pub(crate) async fn init(config: &Config, ledger: &UploadLedger) -> anyhow::Result<InteractionHandler> {
     log::info!("Initializing Discord Module");
 
-    let app_id: Snowflake = std::env::var("DISCORD_APP_ID")
-        .context("DISCORD_APP_ID not set")?.parse()
-        .context("DISCORD_APP_ID is not a valid Snowflake")?;
+    let discord = &config.discord;
+    let app_id: Snowflake = discord.app_id
+        .context("DISCORD_APP_ID not set")?;
 
-    let public_key = std::env::var("DISCORD_PUBLIC_KEY")
+    let public_key = discord.public_key.clone()
         .context("DISCORD_PUBLIC_KEY not set")?;
 
-    let token = std::env::var("DISCORD_TOKEN")
+    let token = discord.token.clone()
         .context("DISCORD_TOKEN not set")?;
 
-    let owner_id: Snowflake = std::env::var("DISCORD_BOT_OWNER_ID")
-        .context("DISCORD_BOT_OWNER_ID not set")?.parse()
-        .context("DISCORD_BOT_OWNER_ID is not a valid Snowflake")?;
+    let owner_id: Snowflake = discord.owner_id
+        .context("DISCORD_BOT_OWNER_ID not set")?;
 
-    let webhooks = std::env::var("DISCORD_WEBHOOK_URLS")
-        .ok()
-        .map(|s| s
-            .split(',')
+    let runtime = RuntimeConfig::load(config.clone(), ledger).await?;
+
+    let mut handler = InteractionHandler::new(app_id, public_key, Some(&token));
+
+    handler.add_data(BotInfo {
+        app_id,
+        owner_id,
+    });
+    handler.add_data(SharedConfig::new(runtime));
+
+    commands::register_commands(&mut handler);
+
+    Ok(handler)
+}
+
+/// Re-reads the configuration and replaces the shared runtime settings, returning a summary of what changed.
+///
+/// Nothing is replaced if any part of the new configuration is invalid.
+pub(crate) async fn reload(handler: &InteractionHandler) -> anyhow::Result<Vec<String>> {
+    let ledger = handler.data.get::<UploadLedger>().unwrap();
+    let shared = handler.data.get::<SharedConfig>().unwrap();
+    let runtime = RuntimeConfig::load(Config::load()?, ledger).await?;
+    let old = shared.current();
+
+    let mut changes = Vec::new();
+
+    if old.profiles != runtime.profiles {
+        changes.push("updated guild profiles (allowed file types, limits, storage prefixes and webhooks)".to_string());
+    }
+    if old.quotas != runtime.quotas {
+        changes.push("updated upload quotas".to_string());
+    }
+    if old.moderation.is_some() != runtime.moderation.is_some() {
+        changes.push(format!("{} upload moderation", if runtime.moderation.is_some() { "enabled" } else { "disabled" }));
+    }
+    if old.options != runtime.options {
+        changes.push("updated upload options".to_string());
+    }
+
+    if !same_provider_settings(&old.config, &runtime.config) {
+        changes.push(format!("reloaded upload provider `{}` -> `{}`", old.uploader, runtime.uploader));
+    }
+    if old.webhook_logo_url != runtime.webhook_logo_url {
+        changes.push("updated webhook logo URL".to_string());
+    }
+
+    shared.replace(runtime);
+
+    Ok(changes)
+}
+
+/// Whether the parts of the config the upload provider is built from are the same.
+fn same_provider_settings(a: &Config, b: &Config) -> bool {
+    let (a, b) = (&a.upload, &b.upload);
+    a.provider == b.provider
+        && a.frontend_url == b.frontend_url
+        && a.http_bearer == b.http_bearer
+        && a.s3 == b.s3
+        && a.filesystem == b.filesystem
+}
+
+/// The Manage Server bit of the member permissions.
+const MANAGE_GUILD: u64 = 1 << 5;
+
+/// Whether the member using an interaction has the Manage Server permission in its guild.
+pub(crate) fn can_manage_guild(ctx: &InteractionContext) -> bool {
+    let permissions = ctx.interaction.member.as_ref()
+        .and_then(|m| m.permissions.as_ref())
+        .and_then(|p| p.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    permissions & MANAGE_GUILD != 0
+}
+
+pub(crate) fn parse_webhooks(urls: Option<&[String]>) -> Option<Vec<Webhook>> {
+    let webhooks = urls
+        .map(|urls| urls
+            .iter()
             .filter_map(|s| match Webhook::new(s.to_string()) {
                 Ok(webhook) => Some(webhook),
                 Err(e) => {
@@ -47,23 +126,9 @@ pub(crate) async fn init() -> anyhow::Result<InteractionHandler> {
             .collect::<Vec<Webhook>>()
         );
 
-    let mut webhook_logo_url = None;
     if let Some(webhooks) = &webhooks {
         log::info!("Parsed {} webhooks", webhooks.len());
-
-        webhook_logo_url = std::env::var("DISCORD_WEBHOOK_LOGO_URL").ok();
     }
 
-    let mut handler = InteractionHandler::new(app_id, public_key, Some(&token));
-
-    handler.add_data(BotInfo {
-        app_id,
-        owner_id,
-        webhooks,
-        webhook_logo_url,
-    });
-
-    commands::register_commands(&mut handler);
-
-    Ok(handler)
+    webhooks
 }
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use chrono::Utc;
+use rusty_interaction::Builder;
+use rusty_interaction::types::components::{ComponentButtonBuilder, ComponentButtonStyle, ComponentRowBuilder};
+use rusty_interaction::types::embed::{EmbedBuilder, EmbedField};
+use rusty_interaction::types::interaction::WebhookMessage;
+use rusty_interaction::types::Snowflake;
+
+use crate::config::Config;
+use crate::discord::webhook::Webhook;
+use crate::ledger::{PreparedUpload, UploadLedger};
+use crate::upload::UploadBody;
+use crate::upload::staging::StagingArea;
+
+pub(crate) const APPROVE_ID: &str = "moderation-approve";
+pub(crate) const REJECT_ID: &str = "moderation-reject";
+
+/// Uploads waiting for a moderator, staged locally and announced to the moderator webhook.
+#[derive(Debug, Clone)]
+pub(crate) struct ModerationQueue {
+    webhook: Webhook,
+    pub(crate) staging: StagingArea,
+    moderator_roles: HashSet<Snowflake>,
+}
+
+impl ModerationQueue {
+    /// Returns `None` if moderation is disabled.
+    pub(crate) fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        let moderation = &config.moderation;
+        if !moderation.enabled.unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let webhook_url = moderation.webhook.clone()
+            .context("MODERATION_WEBHOOK_URL must be set when moderation is enabled")?;
+        let staging_path = moderation.staging_path.clone().unwrap_or("./staging".to_string());
+
+        Ok(Some(ModerationQueue {
+            webhook: Webhook::new(webhook_url)?,
+            staging: StagingArea::open(PathBuf::from(staging_path))?,
+            moderator_roles: moderation.moderator_roles.iter().copied().collect(),
+        }))
+    }
+
+    pub(crate) fn is_moderator(&self, roles: &[Snowflake]) -> bool {
+        roles.iter().any(|role| self.moderator_roles.contains(role))
+    }
+
+    /// Stages the upload and asks the moderators to review it.
+    pub(crate) async fn submit(&self, ledger: &UploadLedger, upload: &PreparedUpload, body: UploadBody, preview_url: &str, avatar_url: Option<String>) -> anyhow::Result<()> {
+        let id = ledger.add_pending(upload)?;
+
+        if let Err(e) = self.announce(id, ledger, upload, body, preview_url, avatar_url).await {
+            ledger.remove_pending(id).ok();
+            self.staging.remove(id).await.ok();
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn announce(&self, id: i64, ledger: &UploadLedger, upload: &PreparedUpload, body: UploadBody, preview_url: &str, avatar_url: Option<String>) -> anyhow::Result<()> {
+        self.staging.stage(id, body).await?;
+
+        let embed = EmbedBuilder::default()
+            .title("Upload waiting for review")
+            .add_field(EmbedField::default()
+                .name("Discord User")
+                .value(format!("`{0}` <@{0}>", upload.user_id))
+            )
+            .add_field(EmbedField::default()
+                .name("Path")
+                .value(format!("`{}`", upload.path))
+            )
+            .add_field(EmbedField::default()
+                .name("File")
+                .value(format!("[{}]({preview_url}) • {} • {}", upload.original_name, upload.content_type, human_bytes::human_bytes(upload.size as f64)))
+            )
+            .timestamp(Utc::now())
+            .build().unwrap();
+
+        let buttons = ComponentRowBuilder::default()
+            .add_button(ComponentButtonBuilder::default()
+                .label("Approve")
+                .custom_id(APPROVE_ID)
+                .style(&ComponentButtonStyle::Success)
+                .build().unwrap()
+            )
+            .add_button(ComponentButtonBuilder::default()
+                .label("Reject")
+                .custom_id(REJECT_ID)
+                .style(&ComponentButtonStyle::Danger)
+                .build().unwrap()
+            )
+            .build().unwrap();
+
+        let message = WebhookMessage {
+            username: Some("PictureBot".to_string()),
+            avatar_url,
+            embeds: Some(vec![embed]),
+            components: Some(vec![buttons]),
+            ..Default::default()
+        };
+
+        let message_id = self.webhook.send_and_wait(&message).await?;
+        ledger.set_pending_message(id, message_id)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use rusty_interaction::types::Snowflake;
+
+use crate::config::{AccessConfig, Config, GuildConfig};
+use crate::discord::parse_webhooks;
+use crate::discord::webhook::Webhook;
+use crate::util::UploadValidator;
+
+/// Upload settings that can differ between guilds.
+#[derive(Debug, Clone)]
+pub(crate) struct GuildProfile {
+    pub(crate) validator: UploadValidator,
+    pub(crate) storage_prefix: Option<String>,
+    pub(crate) webhooks: Option<Vec<Webhook>>,
+    pub(crate) access: UploadAccess,
+}
+
+impl GuildProfile {
+    /// Prepends the storage prefix of this profile to the file name.
+    pub(crate) fn storage_path(&self, file_name: &str) -> String {
+        match &self.storage_prefix {
+            Some(prefix) => format!("{prefix}/{file_name}"),
+            None => file_name.to_string(),
+        }
+    }
+
+    fn webhook_urls(&self) -> Vec<&str> {
+        self.webhooks.iter().flatten().map(|w| w.url.as_str()).collect()
+    }
+}
+
+impl PartialEq for GuildProfile {
+    fn eq(&self, other: &Self) -> bool {
+        self.validator == other.validator
+            && self.storage_prefix == other.storage_prefix
+            && self.webhook_urls() == other.webhook_urls()
+            && self.access == other.access
+    }
+}
+
+/// Role and user lists deciding who may upload.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct UploadAccess {
+    allowed_roles: HashSet<Snowflake>,
+    denied_roles: HashSet<Snowflake>,
+    allowed_users: HashSet<Snowflake>,
+    denied_users: HashSet<Snowflake>,
+}
+
+impl UploadAccess {
+    fn from_config(config: &AccessConfig) -> Self {
+        UploadAccess {
+            allowed_roles: config.allowed_roles.iter().copied().collect(),
+            denied_roles: config.denied_roles.iter().copied().collect(),
+            allowed_users: config.allowed_users.iter().copied().collect(),
+            denied_users: config.denied_users.iter().copied().collect(),
+        }
+    }
+
+    /// Denied users and roles are rejected first, explicitly allowed users skip the role checks.
+    pub(crate) fn check(&self, user_id: Snowflake, roles: &[Snowflake]) -> Result<(), String> {
+        if self.denied_users.contains(&user_id) {
+            return Err("You are not allowed to upload files".to_string());
+        }
+        if self.allowed_users.contains(&user_id) {
+            return Ok(());
+        }
+        if roles.iter().any(|role| self.denied_roles.contains(role)) {
+            return Err("One of your roles is not allowed to upload files".to_string());
+        }
+        if self.allowed_roles.is_empty() && self.allowed_users.is_empty() {
+            return Ok(());
+        }
+        if roles.iter().any(|role| self.allowed_roles.contains(role)) {
+            return Ok(());
+        }
+
+        Err("You need an allowed role to upload files in this server".to_string())
+    }
+}
+
+/// The guild specific profiles, with a default profile for guilds that are not configured.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GuildProfiles {
+    default: GuildProfile,
+    guilds: HashMap<Snowflake, GuildProfile>,
+}
+
+impl GuildProfiles {
+    /// Builds the profiles from the config file, with the settings stored through `/config` taking precedence.
+    pub(crate) fn from_config(config: &Config, stored: &HashMap<Snowflake, GuildConfig>) -> anyhow::Result<Self> {
+        let default = GuildProfile {
+            validator: UploadValidator::from_config(config)?,
+            storage_prefix: None,
+            webhooks: parse_webhooks(config.discord.webhooks.as_deref()),
+            access: UploadAccess::from_config(&config.access),
+        };
+
+        let mut configured = HashMap::with_capacity(config.guilds.len() + stored.len());
+        for (id, guild) in &config.guilds {
+            let guild_id: Snowflake = id.parse()
+                .with_context(|| format!("Guild id {id} is not a valid Snowflake"))?;
+            configured.insert(guild_id, guild.clone());
+        }
+        for (guild_id, settings) in stored {
+            let guild = configured.entry(*guild_id).or_default();
+            if settings.storage_prefix.is_some() {
+                guild.storage_prefix = settings.storage_prefix.clone();
+            }
+            if settings.webhooks.is_some() {
+                guild.webhooks = settings.webhooks.clone();
+            }
+        }
+
+        let mut guilds = HashMap::with_capacity(configured.len());
+        for (guild_id, guild) in &configured {
+            let mut validator = match &guild.extensions {
+                Some(extensions) => UploadValidator::new(config.upload.frontend_url_max_length, extensions, &config.upload.conversions)?,
+                None => default.validator.clone(),
+            };
+            // extensions set through `/config` stay within what the global config allows
+            let stored_extensions = stored.get(guild_id).and_then(|settings| settings.extensions.as_ref());
+            for (extension, limit) in stored_extensions.into_iter().flatten() {
+                if !default.validator.allows(extension) {
+                    log::warn!("Ignoring stored extension {extension} of guild {guild_id}, it is not allowed globally");
+                    continue;
+                }
+                validator.set_extension(extension, smallest_limit(limit.max_size, default.validator.max_size(extension)));
+            }
+            let storage_prefix = guild.storage_prefix.as_ref()
+                .map(|prefix| prefix.trim_matches('/').to_string())
+                .filter(|prefix| !prefix.is_empty());
+            let webhooks = match &guild.webhooks {
+                Some(urls) => parse_webhooks(Some(urls.as_slice())),
+                None => default.webhooks.clone(),
+            };
+            let access = match &guild.access {
+                Some(access) => UploadAccess::from_config(access),
+                None => default.access.clone(),
+            };
+
+            guilds.insert(*guild_id, GuildProfile {
+                validator,
+                storage_prefix,
+                webhooks,
+                access,
+            });
+        }
+        log::info!("Loaded {} guild profiles", guilds.len());
+
+        Ok(GuildProfiles {
+            default,
+            guilds,
+        })
+    }
+
+    pub(crate) fn get(&self, guild_id: Option<Snowflake>) -> &GuildProfile {
+        guild_id.and_then(|id| self.guilds.get(&id)).unwrap_or(&self.default)
+    }
+
+    /// The profile built from the global config only, which limits what guilds can change.
+    pub(crate) fn global(&self) -> &GuildProfile {
+        &self.default
+    }
+}
+
+/// The stricter of two size limits, where `None` means unlimited.
+fn smallest_limit(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use rusty_interaction::types::Snowflake;
+
+use crate::config::{Config, QuotaLimitsConfig};
+use crate::ledger::UploadLedger;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct QuotaLimits {
+    uploads_per_hour: Option<u32>,
+    bytes_per_day: Option<u64>,
+}
+
+impl QuotaLimits {
+    fn with_fallback(config: &QuotaLimitsConfig, fallback: QuotaLimits) -> Self {
+        QuotaLimits {
+            uploads_per_hour: config.uploads_per_hour.or(fallback.uploads_per_hour),
+            bytes_per_day: config.bytes_per_day.or(fallback.bytes_per_day),
+        }
+    }
+
+    /// Combines two limits, keeping the more generous value of each.
+    fn most_generous(self, other: QuotaLimits) -> Self {
+        fn max<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                _ => None,
+            }
+        }
+
+        QuotaLimits {
+            uploads_per_hour: max(self.uploads_per_hour, other.uploads_per_hour),
+            bytes_per_day: max(self.bytes_per_day, other.bytes_per_day),
+        }
+    }
+}
+
+/// Upload limits per user, counted from the uploads recorded in the ledger.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct UploadQuotas {
+    default: QuotaLimits,
+    roles: HashMap<Snowflake, QuotaLimits>,
+}
+
+impl UploadQuotas {
+    pub(crate) fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let default = QuotaLimits {
+            uploads_per_hour: config.quota.uploads_per_hour,
+            bytes_per_day: config.quota.bytes_per_day,
+        };
+
+        let mut roles = HashMap::with_capacity(config.quota.roles.len());
+        for (id, limits) in &config.quota.roles {
+            let role_id: Snowflake = id.parse()
+                .with_context(|| format!("Role id {id} is not a valid Snowflake"))?;
+            roles.insert(role_id, QuotaLimits::with_fallback(limits, default));
+        }
+
+        Ok(UploadQuotas {
+            default,
+            roles,
+        })
+    }
+
+    /// The limits of a member, the most generous of all their roles with a quota, or the global limits.
+    fn limits(&self, roles: &[Snowflake]) -> QuotaLimits {
+        roles.iter()
+            .filter_map(|role| self.roles.get(role).copied())
+            .reduce(QuotaLimits::most_generous)
+            .unwrap_or(self.default)
+    }
+
+    /// Checks whether a user may upload `count` more files with a total of `size` bytes, explaining when they can upload again if not.
+    pub(crate) fn check(&self, ledger: &UploadLedger, user_id: Snowflake, roles: &[Snowflake], count: usize, size: u64) -> Result<(), String> {
+        let limits = self.limits(roles);
+        if limits == QuotaLimits::default() {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let uploads = ledger.uploads_since(user_id, now - Duration::days(1)).map_err(|e| {
+            log::error!("Failed to read uploads of user {user_id} from ledger: {e:#}");
+            "Failed to check your upload quota".to_string()
+        })?;
+
+        if let Some(limit) = limits.uploads_per_hour {
+            let last_hour: Vec<DateTime<Utc>> = uploads.iter()
+                .map(|(uploaded_at, _)| *uploaded_at)
+                .filter(|uploaded_at| *uploaded_at > now - Duration::hours(1))
+                .collect();
+
+            let limit = limit as usize;
+            if limit == 0 {
+                return Err("Your upload quota does not allow any uploads".to_string());
+            }
+            if count > limit {
+                return Err(format!("Your upload quota allows at most {limit} uploads per hour"));
+            }
+            if last_hour.len() + count > limit {
+                let available_at = last_hour[last_hour.len() + count - limit - 1] + Duration::hours(1);
+                return Err(format!("Upload quota of {limit} uploads per hour reached, you can upload again {}", timestamp(available_at)));
+            }
+        }
+
+        if let Some(limit) = limits.bytes_per_day {
+            if size > limit {
+                return Err(format!("Upload is larger than your daily upload quota of {}", human_bytes::human_bytes(limit as f64)));
+            }
+
+            let mut used: u64 = uploads.iter().map(|(_, size)| *size as u64).sum();
+            if used + size > limit {
+                let human_limit = human_bytes::human_bytes(limit as f64);
+                // the oldest uploads leave the window first, find the one after which the file fits
+                for (uploaded_at, uploaded_size) in &uploads {
+                    used -= *uploaded_size as u64;
+                    if used + size <= limit {
+                        return Err(format!("Upload quota of {human_limit} per day reached, you can upload this again {}", timestamp(*uploaded_at + Duration::days(1))));
+                    }
+                }
+                return Err(format!("Upload quota of {human_limit} per day reached"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a time as a relative Discord timestamp.
+fn timestamp(time: DateTime<Utc>) -> String {
+    format!("<t:{}:R>", time.timestamp())
+}
@@ -1,35 +1,147 @@
 use rusty_interaction::Builder;
 use rusty_interaction::handler::InteractionHandler;
-use rusty_interaction::types::application::{ApplicationCommand, ApplicationCommandOption, ApplicationCommandOptionType, SlashCommandDefinitionBuilder};
+use rusty_interaction::types::application::{ApplicationCommand, ApplicationCommandOption, ApplicationCommandOptionChoice, ApplicationCommandOptionType, SlashCommandDefinitionBuilder};
 use rusty_interaction::types::Snowflake;
+use serde_json::json;
+
+use crate::discord::commands::upload::ATTACHMENT_OPTIONS;
+use crate::discord::commands::upload_message;
 
 const BASE_URL: &str = rusty_interaction::BASE_URL;
+const MESSAGE_COMMAND_TYPE: u8 = 3;
 
 pub(crate) async fn update_global_commands(handler: &InteractionHandler, app_id: Snowflake) -> anyhow::Result<()> {
+    let mut upload = SlashCommandDefinitionBuilder::default()
+        .name("upload")
+        .description("Upload an image or video")
+        .add_option(ApplicationCommandOption::default()
+                        .name("file")
+                        .option_type(&ApplicationCommandOptionType::Attachment)
+                        .required(&false)
+                        .description("The image or video to upload"),
+        )
+        .add_option(ApplicationCommandOption::default()
+                        .name("url")
+                        .option_type(&ApplicationCommandOptionType::String)
+                        .required(&false)
+                        .description("The URL of an image or video to upload instead of an attachment"),
+        )
+        .add_option(ApplicationCommandOption::default()
+                        .name("file-name")
+                        .option_type(&ApplicationCommandOptionType::String)
+                        .required(&false)
+                        .description("The desired file name when uploading a single file, otherwise uses the attachment name"),
+        )
+        .add_option(ApplicationCommandOption::default()
+                        .name("on-conflict")
+                        .option_type(&ApplicationCommandOptionType::String)
+                        .required(&false)
+                        .description("What to do when a file with the same name already exists, fails by default")
+                        .add_choice(&choice("Fail", "fail"))
+                        .add_choice(&choice("Add a numeric suffix", "suffix"))
+                        .add_choice(&choice("Overwrite my own file", "overwrite")),
+        );
+    for name in &ATTACHMENT_OPTIONS[1..] {
+        upload = upload.add_option(ApplicationCommandOption::default()
+                                       .name(*name)
+                                       .option_type(&ApplicationCommandOptionType::Attachment)
+                                       .required(&false)
+                                       .description("Another image or video to upload"),
+        );
+    }
+
     let commands: Vec<ApplicationCommand> = vec![
         SlashCommandDefinitionBuilder::default()
             .name("reload")
-            .description("Reload the commands")
+            .description("Reload the commands and configuration")
             .default_permission(false)
             .build().unwrap(),
+        upload.build().unwrap(),
         SlashCommandDefinitionBuilder::default()
-            .name("upload")
-            .description("Upload an image or video")
+            .name("delete")
+            .description("Delete a file you previously uploaded")
             .add_option(ApplicationCommandOption::default()
-                            .name("file")
-                            .option_type(&ApplicationCommandOptionType::Attachment)
+                            .name("file-name")
+                            .option_type(&ApplicationCommandOptionType::String)
                             .required(&true)
-                            .description("The image or video to upload"),
+                            .description("The name of the uploaded file as shown by /list"),
             )
+            .build().unwrap(),
+        SlashCommandDefinitionBuilder::default()
+            .name("list")
+            .description("List your recent uploads")
+            .build().unwrap(),
+        SlashCommandDefinitionBuilder::default()
+            .name("config")
+            .description("Change the upload settings of this server")
             .add_option(ApplicationCommandOption::default()
-                            .name("file-name")
-                            .option_type(&ApplicationCommandOptionType::String)
-                            .required(&false)
-                            .description("The desired file name, otherwise uses the attachment name"),
+                            .name("allow-extension")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Allow uploading files with an extension")
+                            .add_option(ApplicationCommandOption::default()
+                                            .name("extension")
+                                            .option_type(&ApplicationCommandOptionType::String)
+                                            .required(&true)
+                                            .description("The file extension, for example png"),
+                            )
+                            .add_option(ApplicationCommandOption::default()
+                                            .name("max-size")
+                                            .option_type(&ApplicationCommandOptionType::Integer)
+                                            .required(&false)
+                                            .description("The maximum file size in bytes"),
+                            ),
+            )
+            .add_option(ApplicationCommandOption::default()
+                            .name("set-max-size")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Change the maximum file size of an allowed extension")
+                            .add_option(ApplicationCommandOption::default()
+                                            .name("extension")
+                                            .option_type(&ApplicationCommandOptionType::String)
+                                            .required(&true)
+                                            .description("The file extension, for example png"),
+                            )
+                            .add_option(ApplicationCommandOption::default()
+                                            .name("max-size")
+                                            .option_type(&ApplicationCommandOptionType::Integer)
+                                            .required(&true)
+                                            .description("The maximum file size in bytes"),
+                            ),
+            )
+            .add_option(ApplicationCommandOption::default()
+                            .name("set-prefix")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Store uploads of this server under a path prefix")
+                            .add_option(ApplicationCommandOption::default()
+                                            .name("prefix")
+                                            .option_type(&ApplicationCommandOptionType::String)
+                                            .required(&false)
+                                            .description("The path prefix, leave empty to use the configured default"),
+                            ),
+            )
+            .add_option(ApplicationCommandOption::default()
+                            .name("set-webhook")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Announce uploads of this server to a webhook")
+                            .add_option(ApplicationCommandOption::default()
+                                            .name("url")
+                                            .option_type(&ApplicationCommandOptionType::String)
+                                            .required(&false)
+                                            .description("The Discord webhook URL, leave empty to use the configured default"),
+                            ),
             )
             .build().unwrap(),
     ];
 
+    let mut commands = commands.iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    // the builder only knows chat input commands, context menu commands have a type and no description
+    commands.push(json!({
+        "name": upload_message::COMMAND_NAME,
+        "type": MESSAGE_COMMAND_TYPE,
+    }));
+
     let url = format!("{BASE_URL}/applications/{app_id}/commands");
     let response = handler.client().clone().put(url).json(&commands).send().await?;
 
@@ -38,4 +150,11 @@ pub(crate) async fn update_global_commands(handler: &InteractionHandler, app_id:
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn choice(name: &str, value: &str) -> ApplicationCommandOptionChoice {
+    ApplicationCommandOptionChoice {
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::{Arc, RwLock};
+
+use crate::config::Config;
+use crate::discord::moderation::ModerationQueue;
+use crate::discord::profile::GuildProfiles;
+use crate::discord::quota::UploadQuotas;
+use crate::ledger::UploadLedger;
+use crate::upload;
+use crate::upload::Uploader;
+use crate::util::UploadOptions;
+
+/// The settings that `/reload` and `/config` can change while the bot is running.
+#[derive(Debug, Clone)]
+pub(crate) struct RuntimeConfig {
+    pub(crate) config: Config,
+    pub(crate) profiles: GuildProfiles,
+    pub(crate) quotas: UploadQuotas,
+    pub(crate) moderation: Option<ModerationQueue>,
+    pub(crate) options: UploadOptions,
+    pub(crate) uploader: Uploader,
+    pub(crate) webhook_logo_url: Option<String>,
+}
+
+impl RuntimeConfig {
+    /// Builds all settings from the config and the guild settings stored in the ledger, failing if any part is invalid.
+    pub(crate) async fn load(config: Config, ledger: &UploadLedger) -> anyhow::Result<Self> {
+        let profiles = GuildProfiles::from_config(&config, &ledger.guild_settings()?)?;
+        let quotas = UploadQuotas::from_config(&config)?;
+        let moderation = ModerationQueue::from_config(&config)?;
+        let options = UploadOptions::from_config(&config)?;
+        let uploader = upload::init(&config).await?;
+        let webhook_logo_url = config.discord.webhook_logo_url.clone();
+
+        Ok(RuntimeConfig {
+            config,
+            profiles,
+            quotas,
+            moderation,
+            options,
+            uploader,
+            webhook_logo_url,
+        })
+    }
+}
+
+/// The current [RuntimeConfig], shared between all clones of the interaction handler.
+///
+/// Deferred commands run on a clone of the handler, so replacing handler data there would not reach later interactions.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedConfig(Arc<RwLock<Arc<RuntimeConfig>>>);
+
+impl SharedConfig {
+    pub(crate) fn new(runtime: RuntimeConfig) -> Self {
+        SharedConfig(Arc::new(RwLock::new(Arc::new(runtime))))
+    }
+
+    /// The settings at the time of the call, unaffected by later reloads.
+    pub(crate) fn current(&self) -> Arc<RuntimeConfig> {
+        self.0.read().expect("Runtime config lock poisoned").clone()
+    }
+
+    pub(crate) fn replace(&self, runtime: RuntimeConfig) {
+        *self.0.write().expect("Runtime config lock poisoned") = Arc::new(runtime);
+    }
+
+    /// Changes part of the settings, keeping the lock so concurrent updates are applied one after another.
+    pub(crate) fn update(&self, change: impl FnOnce(&mut RuntimeConfig) -> anyhow::Result<()>) -> anyhow::Result<()> {
+        let mut current = self.0.write().expect("Runtime config lock poisoned");
+        let mut runtime = RuntimeConfig::clone(&current);
+        change(&mut runtime)?;
+        *current = Arc::new(runtime);
+
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::Context;
 use rusty_interaction::types::interaction::WebhookMessage;
+use rusty_interaction::types::Snowflake;
 
 #[derive(Debug, Clone)]
 pub(crate) struct Webhook {
@@ -35,5 +36,18 @@ impl Webhook {
 
         Ok(())
     }
-}
 
+    /// Sends the message and waits for Discord to create it, returning the id of the new message.
+    pub(crate) async fn send_and_wait(&self, message: &WebhookMessage) -> anyhow::Result<Snowflake> {
+        let response = self.client.post(&self.url).query(&[("wait", "true")]).json(&message).send().await
+            .context("Failed to send webhook")?;
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to send webhook - {}: {:?}", response.status(), response.text().await?);
+        }
+
+        let created: serde_json::Value = response.json().await.context("Failed to parse webhook response")?;
+        created["id"].as_str()
+            .and_then(|id| id.parse::<Snowflake>().ok())
+            .context("Webhook response has no message id")
+    }
+}
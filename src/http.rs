@@ -1,4 +1,11 @@
 use std::env;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::Context;
+use reqwest::Url;
+use reqwest::redirect::Policy;
+
 use crate::build_info;
 
 static mut USER_AGENT: Option<String> = None;
@@ -21,4 +28,142 @@ pub(crate) fn get_user_agent() -> String {
 
         USER_AGENT.clone().unwrap()
     }
-}
\ No newline at end of file
+}
+
+/// Fetches a user supplied URL, refusing hosts that resolve to private, loopback or otherwise internal addresses.
+///
+/// Redirects are not followed, and the client is pinned to the checked address so the host cannot resolve to another one afterwards.
+pub(crate) async fn fetch_remote(url: &str, timeout: Duration) -> anyhow::Result<reqwest::Response> {
+    let url = Url::parse(url).context("Invalid URL")?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        anyhow::bail!("Only http and https URLs are supported");
+    }
+    let port = url.port_or_known_default().context("URL has no port")?;
+
+    let mut client = reqwest::Client::builder()
+        .user_agent(get_user_agent())
+        .timeout(timeout)
+        .redirect(Policy::none())
+        // a proxy would resolve the host again, bypassing the address checks
+        .no_proxy();
+
+    let host = url.host_str().context("URL has no host")?;
+    match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => {
+            if !is_public(ip) {
+                anyhow::bail!("{ip} is not a public address");
+            }
+        }
+        Err(_) => {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await
+                .with_context(|| format!("Failed to resolve {host}"))?
+                .collect();
+            if addresses.is_empty() || addresses.iter().any(|address| !is_public(address.ip())) {
+                anyhow::bail!("{host} does not resolve to a public address");
+            }
+            client = client.resolve(host, addresses[0]);
+        }
+    }
+
+    let response = client.build().context("Failed to build HTTP client")?
+        .get(url)
+        .send().await
+        .context("Failed to fetch URL")?;
+    if response.status().is_redirection() {
+        anyhow::bail!("URL redirects to another location, please use the final URL");
+    }
+    if !response.status().is_success() {
+        anyhow::bail!("URL returned {}", response.status());
+    }
+
+    Ok(response)
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || first == 0
+                // shared address space used for carrier-grade NAT
+                || (first == 100 && (second & 0xc0) == 64)
+                // benchmarking
+                || (first == 198 && (second & 0xfe) == 18)
+                // reserved for future use
+                || first >= 240)
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(embedded) => is_public(IpAddr::V4(embedded)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local addresses
+                    || (first & 0xfe00) == 0xfc00
+                    // link-local addresses
+                    || (first & 0xffc0) == 0xfe80
+                    // deprecated site-local addresses
+                    || (first & 0xffc0) == 0xfec0)
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 address leads to, for IPv4-mapped, IPv4-compatible, NAT64 and 6to4 addresses.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let [.., a, b, c, d] = ip.octets();
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff, _, _] | [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(Ipv4Addr::new(a, b, c, d)),
+        // IPv4-compatible, except for the unspecified and loopback addresses
+        [0, 0, 0, 0, 0, 0, _, _] if !ip.is_unspecified() && !ip.is_loopback() => Some(Ipv4Addr::new(a, b, c, d)),
+        [0x2002, high, low, ..] => Some(Ipv4Addr::from(((high as u32) << 16) | low as u32)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(address: &str) -> bool {
+        is_public(address.parse().unwrap())
+    }
+
+    #[test]
+    fn allows_public_addresses() {
+        assert!(public("93.184.216.34"));
+        assert!(public("2606:2800:220:1:248:1893:25c8:1946"));
+        assert!(public("::ffff:93.184.216.34"));
+        assert!(public("64:ff9b::5db8:d822"));
+        assert!(public("2002:5db8:d822::1"));
+    }
+
+    #[test]
+    fn blocks_internal_ipv4_ranges() {
+        for address in ["0.0.0.0", "10.0.0.1", "100.64.0.1", "127.0.0.1", "169.254.169.254", "172.16.0.1", "192.0.2.1", "192.168.1.1", "198.18.0.1", "198.19.255.255", "224.0.0.1", "240.0.0.1", "255.255.255.255"] {
+            assert!(!public(address), "{address} should not be public");
+        }
+    }
+
+    #[test]
+    fn blocks_internal_ipv6_ranges() {
+        for address in ["::", "::1", "fc00::1", "fd12:3456::1", "fe80::1", "fec0::1", "ff02::1"] {
+            assert!(!public(address), "{address} should not be public");
+        }
+    }
+
+    #[test]
+    fn blocks_ipv6_addresses_embedding_internal_ipv4() {
+        for address in ["::ffff:127.0.0.1", "::127.0.0.1", "::10.0.0.1", "64:ff9b::7f00:1", "64:ff9b::a9fe:a9fe", "2002:7f00:1::1", "2002:c0a8:101::1"] {
+            assert!(!public(address), "{address} should not be public");
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, params, Row};
+use rusty_interaction::types::Snowflake;
+
+use crate::config::{Config, ExtensionConfig, GuildConfig};
+use crate::util::has_user_prefix;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS uploads (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    guild_id INTEGER,
+    original_name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    url TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    uploaded_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+CREATE INDEX IF NOT EXISTS uploads_user_id ON uploads (user_id);
+CREATE INDEX IF NOT EXISTS uploads_path ON uploads (path);
+CREATE INDEX IF NOT EXISTS uploads_hash ON uploads (hash);
+CREATE TABLE IF NOT EXISTS guild_settings (
+    guild_id INTEGER PRIMARY KEY,
+    storage_prefix TEXT,
+    webhook_url TEXT
+);
+CREATE TABLE IF NOT EXISTS guild_extensions (
+    guild_id INTEGER NOT NULL,
+    extension TEXT NOT NULL,
+    max_size INTEGER,
+    PRIMARY KEY (guild_id, extension)
+);
+CREATE TABLE IF NOT EXISTS pending_uploads (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id INTEGER,
+    user_id INTEGER NOT NULL,
+    guild_id INTEGER,
+    original_name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    uploaded_at TEXT NOT NULL,
+    overwrite INTEGER NOT NULL DEFAULT 0,
+    download_name TEXT
+);
+CREATE INDEX IF NOT EXISTS pending_uploads_message_id ON pending_uploads (message_id);
+CREATE INDEX IF NOT EXISTS pending_uploads_path ON pending_uploads (path);
+";
+
+#[derive(Debug, Clone)]
+pub struct UploadRecord {
+    pub user_id: Snowflake,
+    pub guild_id: Option<Snowflake>,
+    pub original_name: String,
+    pub path: String,
+    pub url: String,
+    pub content_type: String,
+    pub size: usize,
+    pub hash: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// An upload that passed all checks but is not stored by the uploader yet, e.g. while it waits for moderation.
+#[derive(Debug, Clone)]
+pub struct PreparedUpload {
+    pub user_id: Snowflake,
+    pub guild_id: Option<Snowflake>,
+    pub original_name: String,
+    pub path: String,
+    pub content_type: String,
+    pub size: usize,
+    pub hash: String,
+    pub uploaded_at: DateTime<Utc>,
+    /// Replace the file already stored at the path.
+    pub overwrite: bool,
+    /// File name offered to browsers downloading the file, instead of the last segment of the path.
+    pub download_name: Option<String>,
+}
+
+impl PreparedUpload {
+    pub fn into_record(self, url: String) -> UploadRecord {
+        UploadRecord {
+            user_id: self.user_id,
+            guild_id: self.guild_id,
+            original_name: self.original_name,
+            path: self.path,
+            url,
+            content_type: self.content_type,
+            size: self.size,
+            hash: self.hash,
+            uploaded_at: self.uploaded_at,
+        }
+    }
+}
+
+/// Persistent record of every successful upload, backed by an embedded SQLite database.
+#[derive(Debug, Clone)]
+pub struct UploadLedger {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl UploadLedger {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let connection = Connection::open(path)
+            .with_context(|| format!("Failed to open ledger database at {path}"))?;
+        connection.execute_batch(SCHEMA)
+            .context("Failed to initialize ledger database schema")?;
+
+        Ok(UploadLedger {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let path = config.ledger.database_path.clone().unwrap_or("./picturebot.sqlite".to_string());
+        log::info!("Opening upload ledger at {path}");
+
+        UploadLedger::open(path.as_str())
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().expect("Ledger database lock poisoned")
+    }
+
+    pub fn record(&self, record: &UploadRecord) -> anyhow::Result<()> {
+        self.connection().execute(
+            "INSERT INTO uploads (user_id, guild_id, original_name, path, url, content_type, size, hash, uploaded_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                record.user_id as i64,
+                record.guild_id.map(|id| id as i64),
+                record.original_name,
+                record.path,
+                record.url,
+                record.content_type,
+                record.size as i64,
+                record.hash,
+                record.uploaded_at,
+            ],
+        ).context("Failed to insert upload record")?;
+
+        Ok(())
+    }
+
+    /// Most recent uploads of a user that have not been deleted, newest first.
+    pub fn recent_uploads(&self, user_id: Snowflake, limit: usize, offset: usize) -> anyhow::Result<Vec<UploadRecord>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT user_id, guild_id, original_name, path, url, content_type, size, hash, uploaded_at FROM uploads WHERE user_id = ?1 AND deleted_at IS NULL ORDER BY id DESC LIMIT ?2 OFFSET ?3"
+        )?;
+        let records = statement.query_map(params![user_id as i64, limit as i64, offset as i64], record_from_row)?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to query uploads")?;
+
+        Ok(records)
+    }
+
+    pub fn count_uploads(&self, user_id: Snowflake) -> anyhow::Result<usize> {
+        let count: i64 = self.connection().query_row(
+            "SELECT COUNT(*) FROM uploads WHERE user_id = ?1 AND deleted_at IS NULL",
+            params![user_id as i64],
+            |row| row.get(0),
+        ).context("Failed to count uploads")?;
+
+        Ok(count as usize)
+    }
+
+    /// Time and size of every upload of a user since the given time, including deleted and pending ones, oldest first.
+    pub fn uploads_since(&self, user_id: Snowflake, since: DateTime<Utc>) -> anyhow::Result<Vec<(DateTime<Utc>, usize)>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT uploaded_at, size FROM uploads WHERE user_id = ?1 AND uploaded_at > ?2
+             UNION ALL SELECT uploaded_at, size FROM pending_uploads WHERE user_id = ?1 AND uploaded_at > ?2
+             ORDER BY uploaded_at ASC"
+        )?;
+        let uploads = statement.query_map(params![user_id as i64, since], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)))?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to query recent uploads")?;
+
+        Ok(uploads)
+    }
+
+    /// The newest upload with the given content in a guild that has not been deleted, limited to paths under the storage prefix if one is given.
+    pub fn find_by_hash(&self, hash: &str, guild_id: Option<Snowflake>, storage_prefix: Option<&str>) -> anyhow::Result<Option<UploadRecord>> {
+        self.connection().query_row(
+            "SELECT user_id, guild_id, original_name, path, url, content_type, size, hash, uploaded_at FROM uploads
+             WHERE hash = ?1 AND guild_id IS ?2 AND deleted_at IS NULL AND (?3 IS NULL OR substr(path, 1, length(?3) + 1) = ?3 || '/')
+             ORDER BY id DESC LIMIT 1",
+            params![hash, guild_id.map(|id| id as i64), storage_prefix],
+            record_from_row,
+        ).optional().context("Failed to query uploads by hash")
+    }
+
+    /// The user who uploaded the file currently stored at the path, if it was recorded.
+    pub fn owner(&self, path: &str) -> anyhow::Result<Option<Snowflake>> {
+        let owner: Option<i64> = self.connection().query_row(
+            "SELECT user_id FROM uploads WHERE path = ?1 AND deleted_at IS NULL ORDER BY id DESC LIMIT 1",
+            params![path],
+            |row| row.get(0),
+        ).optional().context("Failed to query upload owner")?;
+
+        Ok(owner.map(|id| id as Snowflake))
+    }
+
+    /// Whether the file at the path belongs to the user.
+    ///
+    /// Files uploaded before the ledger existed are matched by the user prefix in their name instead.
+    pub fn is_owned_by(&self, path: &str, user_id: Snowflake) -> anyhow::Result<bool> {
+        match self.owner(path)? {
+            Some(owner) => Ok(owner == user_id),
+            None => Ok(has_user_prefix(path, user_id)),
+        }
+    }
+
+    pub fn mark_deleted(&self, path: &str) -> anyhow::Result<()> {
+        self.connection().execute(
+            "UPDATE uploads SET deleted_at = ?1 WHERE path = ?2 AND deleted_at IS NULL",
+            params![Utc::now(), path],
+        ).context("Failed to mark upload as deleted")?;
+
+        Ok(())
+    }
+
+    /// Stores an upload waiting for moderation, returning its id.
+    pub fn add_pending(&self, upload: &PreparedUpload) -> anyhow::Result<i64> {
+        let connection = self.connection();
+        connection.execute(
+            "INSERT INTO pending_uploads (user_id, guild_id, original_name, path, content_type, size, hash, uploaded_at, overwrite, download_name) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                upload.user_id as i64,
+                upload.guild_id.map(|id| id as i64),
+                upload.original_name,
+                upload.path,
+                upload.content_type,
+                upload.size as i64,
+                upload.hash,
+                upload.uploaded_at,
+                upload.overwrite,
+                upload.download_name,
+            ],
+        ).context("Failed to insert pending upload")?;
+
+        Ok(connection.last_insert_rowid())
+    }
+
+    /// Whether an upload waiting for moderation will be stored at the path.
+    pub fn is_pending(&self, path: &str) -> anyhow::Result<bool> {
+        let count: i64 = self.connection().query_row(
+            "SELECT COUNT(*) FROM pending_uploads WHERE path = ?1",
+            params![path],
+            |row| row.get(0),
+        ).context("Failed to query pending uploads")?;
+
+        Ok(count > 0)
+    }
+
+    /// Links a pending upload to the moderator message reviewing it.
+    pub fn set_pending_message(&self, id: i64, message_id: Snowflake) -> anyhow::Result<()> {
+        self.connection().execute(
+            "UPDATE pending_uploads SET message_id = ?1 WHERE id = ?2",
+            params![message_id as i64, id],
+        ).context("Failed to update pending upload")?;
+
+        Ok(())
+    }
+
+    /// Removes and returns the pending upload reviewed by a moderator message, so it can only be reviewed once.
+    pub fn take_pending(&self, message_id: Snowflake) -> anyhow::Result<Option<(i64, PreparedUpload)>> {
+        let connection = self.connection();
+        let pending = connection.query_row(
+            "SELECT id, user_id, guild_id, original_name, path, content_type, size, hash, uploaded_at, overwrite, download_name FROM pending_uploads WHERE message_id = ?1",
+            params![message_id as i64],
+            |row| Ok((row.get::<_, i64>(0)?, PreparedUpload {
+                user_id: row.get::<_, i64>(1)? as Snowflake,
+                guild_id: row.get::<_, Option<i64>>(2)?.map(|id| id as Snowflake),
+                original_name: row.get(3)?,
+                path: row.get(4)?,
+                content_type: row.get(5)?,
+                size: row.get::<_, i64>(6)? as usize,
+                hash: row.get(7)?,
+                uploaded_at: row.get(8)?,
+                overwrite: row.get(9)?,
+                download_name: row.get(10)?,
+            })),
+        ).optional().context("Failed to query pending upload")?;
+
+        if let Some((id, _)) = &pending {
+            connection.execute("DELETE FROM pending_uploads WHERE id = ?1", params![id])
+                .context("Failed to remove pending upload")?;
+        }
+
+        Ok(pending)
+    }
+
+    /// Puts a pending upload back after its approval failed.
+    pub fn restore_pending(&self, id: i64, message_id: Snowflake, upload: &PreparedUpload) -> anyhow::Result<()> {
+        self.connection().execute(
+            "INSERT INTO pending_uploads (id, message_id, user_id, guild_id, original_name, path, content_type, size, hash, uploaded_at, overwrite, download_name) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                id,
+                message_id as i64,
+                upload.user_id as i64,
+                upload.guild_id.map(|id| id as i64),
+                upload.original_name,
+                upload.path,
+                upload.content_type,
+                upload.size as i64,
+                upload.hash,
+                upload.uploaded_at,
+                upload.overwrite,
+                upload.download_name,
+            ],
+        ).context("Failed to restore pending upload")?;
+
+        Ok(())
+    }
+
+    pub fn remove_pending(&self, id: i64) -> anyhow::Result<()> {
+        self.connection().execute("DELETE FROM pending_uploads WHERE id = ?1", params![id])
+            .context("Failed to remove pending upload")?;
+
+        Ok(())
+    }
+
+    /// Guild settings changed through `/config`, in the same shape as the guild overrides of the config file.
+    pub fn guild_settings(&self) -> anyhow::Result<HashMap<Snowflake, GuildConfig>> {
+        let connection = self.connection();
+        let mut settings: HashMap<Snowflake, GuildConfig> = HashMap::new();
+
+        let mut statement = connection.prepare("SELECT guild_id, storage_prefix, webhook_url FROM guild_settings")?;
+        let rows = statement.query_map([], |row| Ok((
+            row.get::<_, i64>(0)? as Snowflake,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        )))?;
+        for row in rows {
+            let (guild_id, storage_prefix, webhook_url) = row.context("Failed to read guild settings")?;
+            let guild = settings.entry(guild_id).or_default();
+            guild.storage_prefix = storage_prefix;
+            guild.webhooks = webhook_url.map(|url| vec![url]);
+        }
+
+        let mut statement = connection.prepare("SELECT guild_id, extension, max_size FROM guild_extensions")?;
+        let rows = statement.query_map([], |row| Ok((
+            row.get::<_, i64>(0)? as Snowflake,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<i64>>(2)?,
+        )))?;
+        for row in rows {
+            let (guild_id, extension, max_size) = row.context("Failed to read guild extensions")?;
+            settings.entry(guild_id).or_default()
+                .extensions.get_or_insert_with(HashMap::new)
+                .insert(extension, ExtensionConfig { max_size: max_size.map(|size| size as usize) });
+        }
+
+        Ok(settings)
+    }
+
+    pub fn set_guild_extension(&self, guild_id: Snowflake, extension: &str, max_size: Option<usize>) -> anyhow::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO guild_extensions (guild_id, extension, max_size) VALUES (?1, ?2, ?3)",
+            params![guild_id as i64, extension, max_size.map(|size| size as i64)],
+        ).context("Failed to store guild extension")?;
+
+        Ok(())
+    }
+
+    pub fn set_guild_storage_prefix(&self, guild_id: Snowflake, storage_prefix: Option<&str>) -> anyhow::Result<()> {
+        self.connection().execute(
+            "INSERT INTO guild_settings (guild_id, storage_prefix) VALUES (?1, ?2) ON CONFLICT (guild_id) DO UPDATE SET storage_prefix = excluded.storage_prefix",
+            params![guild_id as i64, storage_prefix],
+        ).context("Failed to store guild storage prefix")?;
+
+        Ok(())
+    }
+
+    pub fn set_guild_webhook(&self, guild_id: Snowflake, webhook_url: Option<&str>) -> anyhow::Result<()> {
+        self.connection().execute(
+            "INSERT INTO guild_settings (guild_id, webhook_url) VALUES (?1, ?2) ON CONFLICT (guild_id) DO UPDATE SET webhook_url = excluded.webhook_url",
+            params![guild_id as i64, webhook_url],
+        ).context("Failed to store guild webhook")?;
+
+        Ok(())
+    }
+}
+
+fn record_from_row(row: &Row<'_>) -> rusqlite::Result<UploadRecord> {
+    Ok(UploadRecord {
+        user_id: row.get::<_, i64>(0)? as Snowflake,
+        guild_id: row.get::<_, Option<i64>>(1)?.map(|id| id as Snowflake),
+        original_name: row.get(2)?,
+        path: row.get(3)?,
+        url: row.get(4)?,
+        content_type: row.get(5)?,
+        size: row.get::<_, i64>(6)? as usize,
+        hash: row.get(7)?,
+        uploaded_at: row.get(8)?,
+    })
+}
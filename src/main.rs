@@ -1,10 +1,13 @@
 use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
+use crate::config::Config;
 use crate::discord::BotInfo;
-use crate::util::UploadValidator;
+use crate::ledger::UploadLedger;
 
+mod config;
 mod discord;
+mod ledger;
 mod upload;
 mod util;
 mod http;
@@ -43,12 +46,11 @@ async fn main() -> anyhow::Result<()> {
     }
     log::info!("Starting PictureBot v{}", version());
 
-    let uploader = upload::init().await?;
-    let mut handler = discord::init().await?;
-    handler.add_data(uploader);
+    let config = Config::load()?;
 
-    let validator = UploadValidator::from_env()?;
-    handler.add_data(validator);
+    let ledger = UploadLedger::from_config(&config)?;
+    let mut handler = discord::init(&config, &ledger).await?;
+    handler.add_data(ledger);
 
     let app_info = handler.data.get::<BotInfo>().expect("AppInfo not found");
     log::info!("Discord Application ID: {}", app_info.app_id);
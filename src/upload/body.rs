@@ -0,0 +1,36 @@
+use std::io::Cursor;
+use std::path::Path;
+use std::pin::Pin;
+
+use anyhow::Context;
+use tokio::io::AsyncRead;
+
+/// Content of an upload, read incrementally so providers never need to hold the whole file in memory.
+pub struct UploadBody {
+    pub(crate) reader: Pin<Box<dyn AsyncRead + Send + Sync>>,
+    pub(crate) length: u64,
+}
+
+impl UploadBody {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        let length = bytes.len() as u64;
+
+        UploadBody {
+            reader: Box::pin(Cursor::new(bytes)),
+            length,
+        }
+    }
+
+    pub async fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let file = tokio::fs::File::open(path).await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let length = file.metadata().await
+            .with_context(|| format!("Failed to read metadata of {}", path.display()))?
+            .len();
+
+        Ok(UploadBody {
+            reader: Box::pin(file),
+            length,
+        })
+    }
+}
@@ -0,0 +1,21 @@
+use anyhow::{anyhow, Context};
+use image::DynamicImage;
+
+pub(crate) const WEBP_CONTENT_TYPE: &str = "image/webp";
+
+/// Decodes the image and re-encodes it as lossy WebP with the given quality (0-100).
+pub(crate) async fn convert_to_webp(bytes: Vec<u8>, quality: u8) -> anyhow::Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || {
+        let image = image::load_from_memory(&bytes).context("Failed to decode image")?;
+        // the encoder only accepts 8-bit RGB(A), e.g. grayscale or 16-bit PNGs have to be converted first
+        let image = match image {
+            DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => image,
+            image if image.color().has_alpha() => DynamicImage::ImageRgba8(image.to_rgba8()),
+            image => DynamicImage::ImageRgb8(image.to_rgb8()),
+        };
+        let encoder = webp::Encoder::from_image(&image)
+            .map_err(|e| anyhow!("Failed to prepare WebP encoder: {e}"))?;
+
+        Ok(encoder.encode(quality as f32).to_vec())
+    }).await.context("Image conversion panicked")?
+}
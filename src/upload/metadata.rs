@@ -0,0 +1,102 @@
+use anyhow::Context;
+use bytes::Bytes;
+use img_parts::ImageEXIF;
+use img_parts::jpeg::{Jpeg, markers};
+use img_parts::png::Png;
+use img_parts::webp::{CHUNK_EXIF, CHUNK_XMP, WebP};
+
+/// PNG chunks that can carry EXIF, XMP or free-form text metadata.
+const PNG_METADATA_CHUNKS: [[u8; 4]; 4] = [*b"eXIf", *b"tEXt", *b"zTXt", *b"iTXt"];
+
+const ORIENTATION_TAG: u16 = 0x0112;
+const SHORT_TYPE: u16 = 3;
+
+/// Removes EXIF, XMP and similar metadata from JPEG, PNG and WebP images without re-encoding the pixels.
+///
+/// The EXIF Orientation is kept, as viewers would otherwise show e.g. phone photos rotated.
+///
+/// Returns the resulting bytes and whether any metadata was removed.
+pub(crate) fn strip_metadata(bytes: Vec<u8>, content_type: &str) -> anyhow::Result<(Vec<u8>, bool)> {
+    let (stripped, removed): (Bytes, bool) = match content_type {
+        "image/jpeg" => {
+            let mut jpeg = Jpeg::from_bytes(Bytes::from(bytes)).context("Failed to parse JPEG")?;
+            let orientation = exif_orientation(&jpeg);
+            // APP1 holds EXIF and XMP, APP13 holds IPTC
+            let segments = jpeg.segments().len();
+            jpeg.segments_mut().retain(|segment| segment.marker() != markers::APP1 && segment.marker() != markers::APP13);
+            let removed = jpeg.segments().len() != segments;
+            keep_orientation(&mut jpeg, orientation);
+            (jpeg.encoder().bytes(), removed)
+        }
+        "image/png" => {
+            let mut png = Png::from_bytes(Bytes::from(bytes)).context("Failed to parse PNG")?;
+            let orientation = exif_orientation(&png);
+            let chunks = png.chunks().len();
+            png.chunks_mut().retain(|chunk| !PNG_METADATA_CHUNKS.contains(&chunk.kind()));
+            let removed = png.chunks().len() != chunks;
+            keep_orientation(&mut png, orientation);
+            (png.encoder().bytes(), removed)
+        }
+        "image/webp" => {
+            let mut webp = WebP::from_bytes(Bytes::from(bytes)).context("Failed to parse WebP")?;
+            let orientation = exif_orientation(&webp);
+            let removed = webp.chunk_by_id(CHUNK_EXIF).is_some() || webp.chunk_by_id(CHUNK_XMP).is_some();
+            webp.remove_chunks_by_id(CHUNK_EXIF);
+            webp.remove_chunks_by_id(CHUNK_XMP);
+            keep_orientation(&mut webp, orientation);
+            (webp.encoder().bytes(), removed)
+        }
+        _ => return Ok((bytes, false)),
+    };
+
+    Ok((stripped.to_vec(), removed))
+}
+
+/// The EXIF Orientation (1-8) of the image, if it has one.
+fn exif_orientation(image: &impl ImageEXIF) -> Option<u16> {
+    let exif = image.exif()?;
+    // TIFF header: byte order, magic number and offset of the first IFD
+    let big_endian = match exif.get(..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| {
+        let bytes: [u8; 2] = exif.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| {
+        let bytes: [u8; 4] = exif.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    // each entry is the tag, type, count and a value of up to 4 bytes
+    (0..u16_at(ifd)? as usize)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// Replaces the removed EXIF with a minimal one only holding the orientation, unless the image is upright anyway.
+fn keep_orientation(image: &mut impl ImageEXIF, orientation: Option<u16>) {
+    let orientation = match orientation {
+        Some(orientation) if orientation != 1 => orientation,
+        _ => return,
+    };
+
+    let mut exif = Vec::with_capacity(26);
+    exif.extend_from_slice(b"II*\0");
+    exif.extend_from_slice(&8u32.to_le_bytes());
+    exif.extend_from_slice(&1u16.to_le_bytes());
+    exif.extend_from_slice(&ORIENTATION_TAG.to_le_bytes());
+    exif.extend_from_slice(&SHORT_TYPE.to_le_bytes());
+    exif.extend_from_slice(&1u32.to_le_bytes());
+    exif.extend_from_slice(&orientation.to_le_bytes());
+    exif.extend_from_slice(&[0, 0]);
+    // no further IFDs
+    exif.extend_from_slice(&0u32.to_le_bytes());
+
+    image.set_exif(Some(Bytes::from(exif)));
+}
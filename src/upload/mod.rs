@@ -1,20 +1,34 @@
-use std::{env, fmt};
+use std::fmt;
 use std::fmt::Display;
-use std::str::FromStr;
 
-use anyhow::{anyhow, Context};
+use anyhow::Context;
 use enum_dispatch::enum_dispatch;
 
+use crate::config::Config;
+pub use crate::upload::body::UploadBody;
+use crate::upload::provider::filesystem::FilesystemUploader;
 use crate::upload::provider::http_bearer::HttpBearerUploader;
 use crate::upload::provider::s3::S3Uploader;
 
+mod body;
+pub(crate) mod convert;
+pub(crate) mod metadata;
+pub(crate) mod naming;
 mod provider;
+pub(crate) mod spool;
+pub(crate) mod staging;
+pub(crate) mod thumbnail;
 
-pub async fn init() -> anyhow::Result<Uploader> {
-    let uploader = env::var("UPLOAD_PROVIDER")
-        .context("UPLOAD_PROVIDER not set")?
-        .parse::<Uploader>()
-        .context("Failed to parse UPLOAD_PROVIDER")?;
+pub async fn init(config: &Config) -> anyhow::Result<Uploader> {
+    let provider = config.upload.provider.as_deref()
+        .context("UPLOAD_PROVIDER not set")?;
+
+    let uploader: Uploader = match provider {
+        "http_bearer" => HttpBearerUploader::from_config(config)?.into(),
+        "s3" => S3Uploader::from_config(config)?.into(),
+        "filesystem" => FilesystemUploader::from_config(config)?.into(),
+        _ => anyhow::bail!("Unknown upload provider: {provider}"),
+    };
 
     Ok(uploader)
 }
@@ -25,11 +39,22 @@ pub async fn init() -> anyhow::Result<Uploader> {
 pub enum Uploader {
     HttpBearer(HttpBearerUploader),
     S3(S3Uploader),
+    Filesystem(FilesystemUploader),
 }
 
 #[enum_dispatch]
 pub trait UploaderImpl {
-    async fn upload(&self, path: &str, bytes: Vec<u8>, content_type: &str) -> anyhow::Result<String>;
+    /// Stores a new file, failing if the path is already taken.
+    ///
+    /// The download name is sent as `Content-Disposition` by providers that support per-file headers.
+    async fn upload(&self, path: &str, body: UploadBody, content_type: &str, download_name: Option<&str>) -> anyhow::Result<String>;
+
+    /// Stores a file, replacing any file at the same path.
+    async fn overwrite(&self, path: &str, body: UploadBody, content_type: &str, download_name: Option<&str>) -> anyhow::Result<String>;
+
+    async fn exists(&self, path: &str) -> anyhow::Result<bool>;
+
+    async fn delete(&self, path: &str) -> anyhow::Result<()>;
 
     fn frontend_url(&self, path: &str) -> String;
 }
@@ -39,19 +64,8 @@ impl Display for Uploader {
         let value = match self {
             Uploader::HttpBearer(_) => "http_bearer",
             Uploader::S3(_) => "s3",
+            Uploader::Filesystem(_) => "filesystem",
         };
         write!(f, "{value}")
     }
 }
-
-impl FromStr for Uploader {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "http_bearer" => Ok(HttpBearerUploader::from_env()?.into()),
-            "s3" => Ok(S3Uploader::from_env()?.into()),
-            _ => Err(anyhow!("Unknown upload provider: {s}")),
-        }
-    }
-}
@@ -0,0 +1,254 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use rusty_interaction::types::Snowflake;
+
+use crate::util::user_prefix;
+
+/// The naming scheme used before templates were configurable, e.g. `1234_name.png`.
+pub(crate) const DEFAULT_NAME_TEMPLATE: &str = "{user_prefix}_{name}.{ext}";
+
+/// Short names that do not reveal the name of the file, e.g. `x7Gq2LbZ.png`.
+pub(crate) const RANDOM_NAME_TEMPLATE: &str = "{random}.{ext}";
+
+const RANDOM_LENGTH: usize = 8;
+
+/// Stands in for `{hash8}` until the content of the file is known, so the path has its final length.
+const PENDING_HASH: &str = "00000000";
+
+/// How file names given by users are made safe for storage.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum NameNormalization {
+    /// Refuse names with non-ASCII or disallowed characters.
+    Reject,
+    /// Transliterate and replace unsupported characters, see [slugify].
+    Slugify,
+}
+
+/// Turns a file name into a lowercase ASCII name of letters, digits, dots and underscores.
+///
+/// Unicode is transliterated, e.g. `é` becomes `e`, anything else is replaced by `_`, without repeated or trailing
+/// separators: `Screenshot (3).png` becomes `screenshot_3.png`.
+pub(crate) fn slugify(file_name: &str) -> String {
+    let transliterated = deunicode::deunicode(file_name).to_ascii_lowercase();
+    let segments: Vec<String> = transliterated.split('.')
+        .map(|segment| {
+            let mut slug = String::with_capacity(segment.len());
+            for c in segment.chars() {
+                if c.is_ascii_alphanumeric() {
+                    slug.push(c);
+                } else if !slug.is_empty() && !slug.ends_with('_') {
+                    slug.push('_');
+                }
+            }
+            slug.trim_end_matches('_').to_string()
+        })
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    match segments.as_slice() {
+        [] => "file".to_string(),
+        [ext] if file_name.starts_with('.') => format!("file.{ext}"),
+        _ => segments.join("."),
+    }
+}
+
+/// A `Content-Disposition` header value of the given type, e.g. `inline`, naming the file for downloads.
+///
+/// Non-ASCII names are sent percent-encoded as defined in RFC 6266, with a slugified fallback for older clients.
+pub(crate) fn content_disposition(disposition_type: &str, download_name: &str) -> String {
+    if download_name.is_ascii() && !download_name.contains(['"', '\\']) {
+        return format!("{disposition_type}; filename=\"{download_name}\"");
+    }
+
+    let mut encoded = String::with_capacity(download_name.len() * 3);
+    for byte in download_name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    format!("{disposition_type}; filename=\"{}\"; filename*=UTF-8''{encoded}", slugify(download_name))
+}
+
+/// The values a naming template can refer to.
+pub(crate) struct NameParts<'a> {
+    pub(crate) user_id: Snowflake,
+    pub(crate) guild_id: Option<Snowflake>,
+    pub(crate) date: DateTime<Utc>,
+    /// SHA-256 of the stored content, if already known.
+    pub(crate) hash: Option<&'a str>,
+    pub(crate) random: String,
+    pub(crate) name: &'a str,
+    pub(crate) ext: &'a str,
+}
+
+/// A random alphanumeric identifier for the `{random}` placeholder.
+pub(crate) fn random_id() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), RANDOM_LENGTH)
+}
+
+/// Whether paths from the template can be rendered again with another random id when they are taken.
+pub(crate) fn uses_random(template: &str) -> bool {
+    template.contains("{random}")
+}
+
+/// Whether the template keeps the name given by the user, otherwise it is only offered as download name.
+pub(crate) fn uses_name(template: &str) -> bool {
+    template.contains("{name}")
+}
+
+/// Whether the template can only be rendered once the content of the file is known.
+pub(crate) fn uses_hash(template: &str) -> bool {
+    template.contains("{hash8}")
+}
+
+/// Inserts `-n` before the extension of the file name.
+pub(crate) fn with_suffix(path: &str, n: u32) -> String {
+    let (directory, file_name) = match path.rsplit_once('/') {
+        Some((directory, file_name)) => (format!("{directory}/"), file_name),
+        None => (String::new(), path),
+    };
+
+    match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{directory}{stem}-{n}.{ext}"),
+        _ => format!("{directory}{file_name}-{n}"),
+    }
+}
+
+/// Checks a template from the config by rendering it with example values.
+pub(crate) fn validate(template: &str) -> anyhow::Result<()> {
+    if !template.ends_with(".{ext}") {
+        anyhow::bail!("Naming template must end with `.{{ext}}`: {template}");
+    }
+
+    let parts = NameParts {
+        user_id: 0,
+        guild_id: None,
+        date: Utc::now(),
+        hash: None,
+        random: random_id(),
+        name: "example",
+        ext: "png",
+    };
+    render(template, &parts).map_err(|message| anyhow::anyhow!("{message}: {template}"))?;
+
+    Ok(())
+}
+
+/// Builds the storage path of an upload from the template.
+///
+/// Supported placeholders are `{user_prefix}`, `{user_id}`, `{guild_id}` (`dm` outside of guilds), `{date:FORMAT}` with a
+/// strftime format, `{hash8}` (first 8 hex digits of the SHA-256), `{random}`, `{name}` (without extension) and `{ext}`.
+pub(crate) fn render(template: &str, parts: &NameParts) -> Result<String, String> {
+    let mut path = String::with_capacity(template.len() + parts.name.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        path.push_str(&rest[..start]);
+        let end = start + rest[start..].find('}').ok_or("Unclosed placeholder in naming template")?;
+        let placeholder = &rest[start + 1..end];
+        match placeholder {
+            "user_prefix" => path.push_str(&user_prefix(parts.user_id)),
+            "user_id" => path.push_str(&parts.user_id.to_string()),
+            "guild_id" => match parts.guild_id {
+                Some(guild_id) => path.push_str(&guild_id.to_string()),
+                None => path.push_str("dm"),
+            },
+            "hash8" => path.push_str(parts.hash.and_then(|hash| hash.get(..8)).unwrap_or(PENDING_HASH)),
+            "random" => path.push_str(&parts.random),
+            "name" => path.push_str(parts.name),
+            "ext" => path.push_str(parts.ext),
+            _ => match placeholder.strip_prefix("date:") {
+                Some(format) => write!(path, "{}", parts.date.format(format))
+                    .map_err(|_| format!("Invalid date format `{format}` in naming template"))?,
+                None => return Err(format!("Unknown placeholder `{{{placeholder}}}` in naming template")),
+            },
+        }
+        rest = &rest[end + 1..];
+    }
+    path.push_str(rest);
+
+    if path.starts_with('/') || path.split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..") {
+        return Err(format!("Invalid file path `{path}`"));
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn parts(name: &str) -> NameParts<'_> {
+        NameParts {
+            user_id: 123456789,
+            guild_id: Some(42),
+            date: Utc.with_ymd_and_hms(2024, 3, 5, 12, 0, 0).unwrap(),
+            hash: None,
+            random: "x7Gq2LbZ".to_string(),
+            name,
+            ext: "png",
+        }
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        assert_eq!(render(DEFAULT_NAME_TEMPLATE, &parts("cat")).unwrap(), "6789_cat.png");
+        assert_eq!(render(RANDOM_NAME_TEMPLATE, &parts("cat")).unwrap(), "x7Gq2LbZ.png");
+        assert_eq!(render("{guild_id}/{user_id}/{name}.{ext}", &parts("cat")).unwrap(), "42/123456789/cat.png");
+    }
+
+    #[test]
+    fn renders_dates_with_slashes_as_directories() {
+        assert_eq!(render("{date:%Y/%m}/{name}.{ext}", &parts("cat")).unwrap(), "2024/03/cat.png");
+    }
+
+    #[test]
+    fn renders_pending_and_final_hash() {
+        let mut parts = parts("cat");
+        assert_eq!(render("{hash8}.{ext}", &parts).unwrap(), "00000000.png");
+
+        parts.hash = Some("0123456789abcdef");
+        assert_eq!(render("{hash8}.{ext}", &parts).unwrap(), "01234567.png");
+    }
+
+    #[test]
+    fn rejects_path_traversal() {
+        assert!(render("{name}.{ext}", &parts("../../etc/passwd")).is_err());
+        assert!(render("uploads/{name}/x.{ext}", &parts("..")).is_err());
+        assert!(render("/{name}.{ext}", &parts("cat")).is_err());
+        assert!(render("uploads//{name}.{ext}", &parts("cat")).is_err());
+        assert!(render("{name}/{user_prefix}.{ext}", &parts("")).is_err());
+    }
+
+    #[test]
+    fn slugifies_file_names() {
+        assert_eq!(slugify("Screenshot (3).png"), "screenshot_3.png");
+        assert_eq!(slugify("Café Crème.JPG"), "cafe_creme.jpg");
+        assert_eq!(slugify("a  --  b..tar.gz"), "a_b.tar.gz");
+        assert_eq!(slugify(".png"), "file.png");
+        assert_eq!(slugify("***"), "file");
+    }
+
+    #[test]
+    fn suffixes_file_names() {
+        assert_eq!(with_suffix("6789_cat.png", 1), "6789_cat-1.png");
+        assert_eq!(with_suffix("2024/03/cat.tar.gz", 2), "2024/03/cat.tar-2.gz");
+        assert_eq!(with_suffix("v1.2/cat", 3), "v1.2/cat-3");
+        assert_eq!(with_suffix(".hidden", 4), ".hidden-4");
+    }
+
+    #[test]
+    fn validates_templates() {
+        assert!(validate(DEFAULT_NAME_TEMPLATE).is_ok());
+        assert!(validate("{date:%Y/%m}/{hash8}.{ext}").is_ok());
+        assert!(validate("{name}.png").is_err());
+        assert!(validate("{unknown}.{ext}").is_err());
+        assert!(validate("{name.{ext}").is_err());
+        assert!(validate("../{name}.{ext}").is_err());
+    }
+}
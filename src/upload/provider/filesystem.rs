@@ -0,0 +1,134 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Context;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::Config;
+use crate::upload::{UploadBody, UploaderImpl};
+
+/// Distinguishes the temporary files of concurrent writes to the same path.
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone)]
+pub struct FilesystemUploader {
+    storage_path: PathBuf,
+    frontend_url: String,
+}
+
+impl FilesystemUploader {
+    pub(crate) fn new(storage_path: PathBuf, frontend_url: &str) -> anyhow::Result<Self> {
+        if !storage_path.is_dir() {
+            anyhow::bail!("Storage path {} is not a directory", storage_path.display());
+        }
+
+        let mut frontend_url_mut = frontend_url.to_string();
+        if frontend_url_mut.ends_with('/') {
+            frontend_url_mut.pop();
+        }
+
+        Ok(FilesystemUploader {
+            storage_path,
+            frontend_url: frontend_url_mut,
+        })
+    }
+
+    pub(crate) fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let storage_path = config.upload.filesystem.storage_path.clone()
+            .context("UPLOAD_STORAGE_PATH must be set when using filesystem storage")?;
+
+        let frontend_url = config.upload.frontend_url.clone()
+            .context("UPLOAD_FRONTEND_URL must be set when using filesystem storage")?;
+
+        FilesystemUploader::new(PathBuf::from(storage_path), frontend_url.as_str())
+    }
+
+    /// Writes to a temporary file in the same directory first, so the file appears at the target path at once.
+    async fn store(&self, path: &str, body: UploadBody, replace: bool) -> anyhow::Result<String> {
+        let target = self.target_path(path)?;
+        let parent = target.parent().context("Target path has no parent directory")?;
+        tokio::fs::create_dir_all(parent).await
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+
+        let file_name = target.file_name().context("Target path has no file name")?.to_string_lossy();
+        let temp_id = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp_file = parent.join(format!(".{file_name}.{}.{temp_id}.tmp", std::process::id()));
+        if let Err(e) = write_file(&temp_file, body).await {
+            tokio::fs::remove_file(&temp_file).await.ok();
+            return Err(e);
+        }
+
+        // a rename replaces existing files, while a hard link fails if the target was created in the meantime
+        let result = if replace {
+            tokio::fs::rename(&temp_file, &target).await
+        } else {
+            tokio::fs::hard_link(&temp_file, &target).await
+        };
+        if !replace || result.is_err() {
+            tokio::fs::remove_file(&temp_file).await.ok();
+        }
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => anyhow::bail!("File already exists"),
+            result => result.with_context(|| format!("Failed to move file to {}", target.display()))?,
+        }
+        log::debug!("Stored file at {}", target.display());
+
+        Ok(self.frontend_url(path))
+    }
+
+    fn target_path(&self, path: &str) -> anyhow::Result<PathBuf> {
+        let relative = path.trim_start_matches('/');
+        if relative.split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..") {
+            anyhow::bail!("Invalid file path: {path}");
+        }
+
+        Ok(self.storage_path.join(relative))
+    }
+}
+
+impl UploaderImpl for FilesystemUploader {
+    /// Files are served by another web server, which decides on the headers itself, so the download name is not used.
+    async fn upload(&self, path: &str, body: UploadBody, _content_type: &str, _download_name: Option<&str>) -> anyhow::Result<String> {
+        self.store(path, body, false).await
+    }
+
+    async fn overwrite(&self, path: &str, body: UploadBody, _content_type: &str, _download_name: Option<&str>) -> anyhow::Result<String> {
+        self.store(path, body, true).await
+    }
+
+    async fn exists(&self, path: &str) -> anyhow::Result<bool> {
+        let target = self.target_path(path)?;
+        tokio::fs::try_exists(&target).await
+            .with_context(|| format!("Failed to check if {} exists", target.display()))
+    }
+
+    async fn delete(&self, path: &str) -> anyhow::Result<()> {
+        let target = self.target_path(path)?;
+        if !tokio::fs::try_exists(&target).await
+            .with_context(|| format!("Failed to check if {} exists", target.display()))? {
+            anyhow::bail!("File does not exist");
+        }
+
+        tokio::fs::remove_file(&target).await
+            .with_context(|| format!("Failed to delete {}", target.display()))?;
+        log::debug!("Deleted file at {}", target.display());
+
+        Ok(())
+    }
+
+    fn frontend_url(&self, path: &str) -> String {
+        format!("{}/{}", self.frontend_url, path.trim_start_matches('/'))
+    }
+}
+
+async fn write_file(target: &Path, body: UploadBody) -> anyhow::Result<()> {
+    let mut reader = body.reader;
+    let mut file = tokio::fs::File::create(target).await
+        .with_context(|| format!("Failed to create temporary file {}", target.display()))?;
+    tokio::io::copy(&mut reader, &mut file).await
+        .with_context(|| format!("Failed to write temporary file {}", target.display()))?;
+    file.flush().await
+        .with_context(|| format!("Failed to write temporary file {}", target.display()))?;
+
+    Ok(())
+}
@@ -1,11 +1,13 @@
-use std::env;
 use std::str::FromStr;
 
 use anyhow::Context;
 use reqwest::header::HeaderName;
+use tokio_util::io::ReaderStream;
 
+use crate::config::Config;
 use crate::http;
-use crate::upload::UploaderImpl;
+use crate::upload::naming::content_disposition;
+use crate::upload::{UploadBody, UploaderImpl};
 
 #[derive(Debug, Clone)]
 pub struct HttpBearerUploader {
@@ -49,16 +51,17 @@ impl HttpBearerUploader {
         })
     }
 
-    pub(crate) fn from_env() -> anyhow::Result<Self> {
-        let upload_url = env::var("UPLOAD_URL")
+    pub(crate) fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let http_bearer = &config.upload.http_bearer;
+        let upload_url = http_bearer.url.clone()
             .context("UPLOAD_URL is not set")?;
 
-        let mut frontend_url = env::var("UPLOAD_FRONTEND_URL").unwrap_or(upload_url.clone());
+        let mut frontend_url = config.upload.frontend_url.clone().unwrap_or(upload_url.clone());
         if frontend_url.ends_with('/') {
             frontend_url.pop();
         }
-        let auth_header_name = env::var("UPLOAD_AUTH_HEADER_NAME").ok();
-        let auth_header_value = env::var("UPLOAD_AUTH_HEADER_VALUE")
+        let auth_header_name = http_bearer.auth_header_name.clone();
+        let auth_header_value = http_bearer.auth_header_value.clone()
             .context("UPLOAD_AUTH_HEADER_VALUE is not set")?;
 
         HttpBearerUploader::new(upload_url, frontend_url, auth_header_name, auth_header_value)
@@ -66,25 +69,58 @@ impl HttpBearerUploader {
 }
 
 impl UploaderImpl for HttpBearerUploader {
-    async fn upload(&self, path: &str, bytes: Vec<u8>, content_type: &str) -> anyhow::Result<String> {
+    async fn upload(&self, path: &str, body: UploadBody, content_type: &str, download_name: Option<&str>) -> anyhow::Result<String> {
+        if self.exists(path).await? {
+            anyhow::bail!("File already exists at {}", self.frontend_url(path));
+        }
+
+        self.overwrite(path, body, content_type, download_name).await
+    }
 
+    async fn overwrite(&self, path: &str, body: UploadBody, content_type: &str, download_name: Option<&str>) -> anyhow::Result<String> {
         let target_url = format!("{}/{}", self.upload_url, path);
 
         let frontend_url = self.frontend_url(path);
-        let response = self.client.get(&target_url).send().await
-            .with_context(|| format!("Failed to make GET request to {frontend_url}"))?;
-        if response.status() != reqwest::StatusCode::NOT_FOUND {
-            anyhow::bail!("File already exists at {frontend_url}");
-        }
-
-        self.client.put(&target_url)
+        let mut request = self.client.put(&target_url)
             .header(reqwest::header::CONTENT_TYPE, content_type)
-            .body(bytes)
+            .header(reqwest::header::CONTENT_LENGTH, body.length);
+        if let Some(download_name) = download_name {
+            request = request.header(reqwest::header::CONTENT_DISPOSITION, content_disposition("inline", download_name));
+        }
+        let response = request.body(reqwest::Body::wrap_stream(ReaderStream::new(body.reader)))
             .send()
             .await
             .with_context(|| format!("Failed to make PUT request to {frontend_url}"))?;
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to upload {frontend_url}: {}", response.status());
+        }
+
+        Ok(frontend_url)
+    }
+
+    async fn exists(&self, path: &str) -> anyhow::Result<bool> {
+        let target_url = format!("{}/{}", self.upload_url, path);
+
+        let response = self.client.get(&target_url).send().await
+            .with_context(|| format!("Failed to make GET request to {}", self.frontend_url(path)))?;
+
+        Ok(response.status() != reqwest::StatusCode::NOT_FOUND)
+    }
+
+    async fn delete(&self, path: &str) -> anyhow::Result<()> {
+        let target_url = format!("{}/{}", self.upload_url, path);
+
+        let frontend_url = self.frontend_url(path);
+        let response = self.client.delete(&target_url).send().await
+            .with_context(|| format!("Failed to make DELETE request to {frontend_url}"))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            anyhow::bail!("File does not exist at {frontend_url}");
+        }
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to delete {frontend_url}: {}", response.status());
+        }
 
-        Ok(self.frontend_url(path))
+        Ok(())
     }
 
     fn frontend_url(&self, path: &str) -> String {
@@ -1,2 +1,3 @@
+pub(crate) mod filesystem;
 pub(crate) mod http_bearer;
 pub(crate) mod s3;
\ No newline at end of file
@@ -1,4 +1,3 @@
-use std::env;
 use std::str::FromStr;
 
 use anyhow::Context;
@@ -7,23 +6,38 @@ use s3::creds::Credentials;
 use s3::error::S3Error;
 use s3::serde_types::HeadObjectResult;
 
-use crate::upload::UploaderImpl;
+use crate::config::Config;
+use crate::upload::{UploadBody, UploaderImpl};
+use crate::upload::naming::content_disposition;
 
 #[derive(Debug, Clone)]
 pub struct S3Uploader {
     bucket: Bucket,
+    /// Same bucket, with extra headers that only apply when storing uploaded files.
+    upload_bucket: Bucket,
+    /// `inline` or `attachment`, taken from the configured Content-Disposition.
+    disposition_type: String,
     storage_path: String,
     frontend_url: String,
 }
 
 impl S3Uploader {
-    pub(crate) fn new(frontend_url: &str, credentials: Credentials, region: Region, bucket_name: &str, use_path_style: bool, storage_path: Option<&str>) -> anyhow::Result<Self> {
+    pub(crate) fn new(frontend_url: &str, credentials: Credentials, region: Region, bucket_name: &str, use_path_style: bool, storage_path: Option<&str>, upload_headers: Vec<(String, String)>) -> anyhow::Result<Self> {
         let mut bucket = Bucket::new(bucket_name, region, credentials)?;
 
         if use_path_style {
             bucket.set_path_style();
         }
 
+        let mut upload_bucket = bucket.clone();
+        let mut disposition_type = "inline".to_string();
+        for (name, value) in &upload_headers {
+            upload_bucket.add_header(name, value);
+            if name.eq_ignore_ascii_case("Content-Disposition") {
+                disposition_type = value.split(';').next().unwrap_or_default().trim().to_string();
+            }
+        }
+
         let mut storage_path_mut = storage_path.unwrap_or("").to_string();
 
         if storage_path_mut.ends_with('/') {
@@ -37,14 +51,17 @@ impl S3Uploader {
 
         Ok(S3Uploader {
             bucket,
+            upload_bucket,
+            disposition_type,
             storage_path: storage_path_mut,
             frontend_url: frontend_url_mut,
         })
     }
 
-    pub(crate) fn from_env() -> anyhow::Result<Self> {
-        let s3_region = env::var("S3_REGION").ok();
-        let s3_url = env::var("S3_URL").ok();
+    pub(crate) fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let s3 = &config.upload.s3;
+        let s3_region = s3.region.clone();
+        let s3_url = s3.url.clone();
 
         if s3_region.is_none() && s3_url.is_none() {
             anyhow::bail!("One of S3_REGION or S3_URL must be set when using S3 storage!");
@@ -62,47 +79,89 @@ impl S3Uploader {
             }
         };
 
-        let access_key = env::var("S3_ACCESS_KEY_ID")
+        let access_key = s3.access_key_id.as_deref()
             .context("S3_ACCESS_KEY_ID is not set")?;
-        let secret_key = env::var("S3_SECRET_ACCESS_KEY")
+        let secret_key = s3.secret_access_key.as_deref()
             .context("S3_SECRET_ACCESS_KEY is not set")?;
 
-        let security_token = env::var("S3_SECURITY_TOKEN").ok();
-        let session_token = env::var("S3_SESSION_TOKEN").ok();
-        let profile = env::var("S3_PROFILE").ok();
-
-        let credentials = Credentials::new(Some(access_key.as_str()), Some(secret_key.as_str()), security_token.as_deref(), session_token.as_deref(), profile.as_deref())
+        let credentials = Credentials::new(Some(access_key), Some(secret_key), s3.security_token.as_deref(), s3.session_token.as_deref(), s3.profile.as_deref())
             .context("Failed to create S3 credentials")?;
 
-        let bucket_name = env::var("S3_BUCKET_NAME")
+        let bucket_name = s3.bucket_name.as_deref()
             .context("S3_BUCKET_NAME is not set")?;
 
-        let use_path_style = env::var("S3_USE_PATH_STYLE")
-            .map(|s| s.parse::<bool>()).ok().transpose()
-            .context("Failed to parse S3_USE_PATH_STYLE")?.unwrap_or(false);
+        let use_path_style = s3.use_path_style.unwrap_or(false);
 
-        let storage_path = env::var("S3_STORAGE_PATH").unwrap_or("".to_string());
+        let storage_path = s3.storage_path.as_deref().unwrap_or("");
 
-        let frontend_url = env::var("UPLOAD_FRONTEND_URL")
+        let frontend_url = config.upload.frontend_url.as_deref()
             .context("UPLOAD_FRONTEND_URL must be set when using S3 storage")?;
 
-        S3Uploader::new(frontend_url.as_str(), credentials, region, &bucket_name, use_path_style, Some(storage_path.as_str()))
+        let mut upload_headers = Vec::new();
+        if let Some(cache_control) = &s3.cache_control {
+            upload_headers.push(("Cache-Control".to_string(), cache_control.clone()));
+        }
+        if let Some(content_disposition) = &s3.content_disposition {
+            upload_headers.push(("Content-Disposition".to_string(), content_disposition.clone()));
+        }
+        if let Some(acl) = &s3.acl {
+            upload_headers.push(("x-amz-acl".to_string(), acl.clone()));
+        }
+
+        S3Uploader::new(frontend_url, credentials, region, bucket_name, use_path_style, Some(storage_path), upload_headers)
+    }
+
+    async fn store(&self, path: &str, body: UploadBody, content_type: &str, download_name: Option<&str>) -> anyhow::Result<String> {
+        let mut reader = body.reader;
+        let mut bucket = self.upload_bucket.clone();
+        if let Some(download_name) = download_name {
+            // keeps the configured disposition type and only adds the name for this file
+            bucket.add_header("Content-Disposition", &content_disposition(&self.disposition_type, download_name));
+        }
+        // switches to a multipart upload for anything larger than a single chunk
+        let status = bucket.put_object_stream_with_content_type(&mut reader, path, content_type).await?;
+        if !(200..300).contains(&status) {
+            anyhow::bail!("Failed to upload file: status {status}");
+        }
+        log::debug!("Uploaded file to s3://{bucket}@{path}", bucket = &self.bucket.name);
+        Ok(self.frontend_url(path))
     }
 }
 
 impl UploaderImpl for S3Uploader {
-    async fn upload(&self, path: &str, bytes: Vec<u8>, _content_type: &str) -> anyhow::Result<String> {
+    async fn upload(&self, path: &str, body: UploadBody, content_type: &str, download_name: Option<&str>) -> anyhow::Result<String> {
         let path = format!("{}/{}", self.storage_path, path);
         match check_file_exists(&self.bucket, path.as_str()).await? {
-            None => {
-                self.bucket.put_object(path.as_str(), bytes.as_slice()).await?;
-                log::debug!("Uploaded file to s3://{bucket}@{path}", bucket = &self.bucket.name);
-                Ok(self.frontend_url(path.as_str()))
-            }
+            None => self.store(path.as_str(), body, content_type, download_name).await,
             Some(_) => anyhow::bail!("File already exists"),
         }
     }
 
+    async fn overwrite(&self, path: &str, body: UploadBody, content_type: &str, download_name: Option<&str>) -> anyhow::Result<String> {
+        let path = format!("{}/{}", self.storage_path, path);
+        self.store(path.as_str(), body, content_type, download_name).await
+    }
+
+    async fn exists(&self, path: &str) -> anyhow::Result<bool> {
+        let path = format!("{}/{}", self.storage_path, path);
+        Ok(check_file_exists(&self.bucket, path.as_str()).await?.is_some())
+    }
+
+    async fn delete(&self, path: &str) -> anyhow::Result<()> {
+        let path = format!("{}/{}", self.storage_path, path);
+        match check_file_exists(&self.bucket, path.as_str()).await? {
+            Some(_) => {
+                let response = self.bucket.delete_object(path.as_str()).await?;
+                if !(200..300).contains(&response.status_code()) {
+                    anyhow::bail!("Failed to delete file: status {}", response.status_code());
+                }
+                log::debug!("Deleted file s3://{bucket}@{path}", bucket = &self.bucket.name);
+                Ok(())
+            }
+            None => anyhow::bail!("File does not exist"),
+        }
+    }
+
     fn frontend_url(&self, path: &str) -> String {
         format!("{}/{}", self.frontend_url, path.trim_start_matches('/'))
     }
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use crate::upload::body::UploadBody;
+
+/// Number of leading bytes kept in memory for content sniffing.
+const HEAD_LENGTH: usize = 8192;
+
+static SPOOL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A downloaded file, buffered in a temporary file on disk rather than in memory.
+///
+/// The file is hashed while it is written, and removed again when this is dropped.
+#[derive(Debug)]
+pub(crate) struct SpooledFile {
+    path: PathBuf,
+    pub(crate) size: u64,
+    pub(crate) hash: String,
+    pub(crate) head: Vec<u8>,
+}
+
+impl SpooledFile {
+    pub(crate) async fn download(mut response: reqwest::Response, max_size: Option<u64>) -> anyhow::Result<Self> {
+        let id = SPOOL_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("picturebot-{}-{id}.part", std::process::id()));
+
+        let mut spooled = SpooledFile {
+            path,
+            size: 0,
+            hash: String::new(),
+            head: Vec::with_capacity(HEAD_LENGTH),
+        };
+
+        let mut file = tokio::fs::File::create(&spooled.path).await
+            .with_context(|| format!("Failed to create temporary file {}", spooled.path.display()))?;
+        let mut hasher = Sha256::new();
+
+        while let Some(chunk) = response.chunk().await.context("Failed to read response body")? {
+            spooled.size += chunk.len() as u64;
+            if let Some(max_size) = max_size {
+                if spooled.size > max_size {
+                    anyhow::bail!("File too big! Maximum allowed size is {}", human_bytes::human_bytes(max_size as f64));
+                }
+            }
+
+            if spooled.head.len() < HEAD_LENGTH {
+                let missing = HEAD_LENGTH - spooled.head.len();
+                spooled.head.extend_from_slice(&chunk[..missing.min(chunk.len())]);
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk).await.context("Failed to write temporary file")?;
+        }
+        file.flush().await.context("Failed to write temporary file")?;
+
+        spooled.hash = hex::encode(hasher.finalize());
+        Ok(spooled)
+    }
+
+    /// Reads the whole file into memory, for processing steps that need the complete content.
+    pub(crate) async fn read(&self) -> anyhow::Result<Vec<u8>> {
+        tokio::fs::read(&self.path).await
+            .with_context(|| format!("Failed to read temporary file {}", self.path.display()))
+    }
+
+    pub(crate) async fn body(&self) -> anyhow::Result<UploadBody> {
+        UploadBody::from_file(&self.path).await
+    }
+}
+
+impl Drop for SpooledFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to remove temporary file {}: {e}", self.path.display());
+        }
+    }
+}
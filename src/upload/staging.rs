@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use tokio::io::AsyncWriteExt;
+
+use crate::upload::body::UploadBody;
+
+/// Local directory holding uploads that must not be public yet.
+#[derive(Debug, Clone)]
+pub(crate) struct StagingArea {
+    path: PathBuf,
+}
+
+impl StagingArea {
+    pub(crate) fn open(path: PathBuf) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create staging directory {}", path.display()))?;
+
+        Ok(StagingArea {
+            path,
+        })
+    }
+
+    fn file(&self, id: i64) -> PathBuf {
+        self.path.join(format!("{id}.staged"))
+    }
+
+    pub(crate) async fn stage(&self, id: i64, mut body: UploadBody) -> anyhow::Result<()> {
+        let file_path = self.file(id);
+        let mut file = tokio::fs::File::create(&file_path).await
+            .with_context(|| format!("Failed to create staged file {}", file_path.display()))?;
+
+        tokio::io::copy(&mut body.reader, &mut file).await
+            .with_context(|| format!("Failed to write staged file {}", file_path.display()))?;
+        file.flush().await
+            .with_context(|| format!("Failed to write staged file {}", file_path.display()))?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn read(&self, id: i64) -> anyhow::Result<Vec<u8>> {
+        let file_path = self.file(id);
+        tokio::fs::read(&file_path).await
+            .with_context(|| format!("Failed to read staged file {}", file_path.display()))
+    }
+
+    pub(crate) async fn body(&self, id: i64) -> anyhow::Result<UploadBody> {
+        UploadBody::from_file(&self.file(id)).await
+    }
+
+    pub(crate) async fn remove(&self, id: i64) -> anyhow::Result<()> {
+        let file_path = self.file(id);
+        tokio::fs::remove_file(&file_path).await
+            .with_context(|| format!("Failed to remove staged file {}", file_path.display()))
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::Cursor;
+
+use anyhow::Context;
+use image::ImageFormat;
+use image::imageops::FilterType;
+
+pub(crate) const THUMBNAIL_CONTENT_TYPE: &str = "image/webp";
+
+#[derive(Debug, Clone)]
+pub(crate) struct Thumbnail {
+    pub(crate) width: u32,
+    pub(crate) bytes: Vec<u8>,
+}
+
+/// Downscales the image to each of the given widths, skipping widths that are not smaller than the original.
+pub(crate) async fn generate_thumbnails(bytes: &[u8], widths: &[u32]) -> anyhow::Result<Vec<Thumbnail>> {
+    let bytes = bytes.to_vec();
+    let widths = widths.to_vec();
+
+    tokio::task::spawn_blocking(move || {
+        let image = image::load_from_memory(&bytes).context("Failed to decode image")?;
+
+        let mut thumbnails = Vec::with_capacity(widths.len());
+        for width in widths {
+            if width >= image.width() {
+                continue;
+            }
+
+            let height = ((image.height() as u64 * width as u64) / image.width() as u64).max(1) as u32;
+            let resized = image.resize_exact(width, height, FilterType::Lanczos3);
+
+            let mut encoded = Cursor::new(Vec::new());
+            resized.write_to(&mut encoded, ImageFormat::WebP)
+                .with_context(|| format!("Failed to encode {width}px thumbnail"))?;
+
+            thumbnails.push(Thumbnail {
+                width,
+                bytes: encoded.into_inner(),
+            });
+        }
+
+        Ok(thumbnails)
+    }).await.context("Thumbnail generation panicked")?
+}
+
+/// Builds the storage path of a thumbnail from the template, next to the original file.
+///
+/// Supported placeholders are `{name}` (file name without extension), `{ext}` and `{width}`.
+pub(crate) fn thumbnail_path(template: &str, path: &str, width: u32) -> String {
+    let (directory, file_name) = match path.rsplit_once('/') {
+        Some((directory, file_name)) => (Some(directory), file_name),
+        None => (None, path),
+    };
+    let (name, extension) = file_name.rsplit_once('.').unwrap_or((file_name, ""));
+
+    let thumbnail_name = template
+        .replace("{name}", name)
+        .replace("{ext}", extension)
+        .replace("{width}", width.to_string().as_str());
+
+    match directory {
+        Some(directory) => format!("{directory}/{thumbnail_name}"),
+        None => thumbnail_name,
+    }
+}
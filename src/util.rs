@@ -1,46 +1,155 @@
 use std::collections::HashMap;
-use std::env;
+use std::time::Duration;
 
-use anyhow::Context;
+use rusty_interaction::types::Snowflake;
+use sha2::{Digest, Sha256};
+
+use crate::config::{Config, ExtensionConfig};
+use crate::upload::naming::{self, NameNormalization, DEFAULT_NAME_TEMPLATE, RANDOM_NAME_TEMPLATE};
+
+/// The last 4 digits of the user id, used to namespace uploaded files per user.
+pub fn user_prefix(user_id: Snowflake) -> String {
+    let user_id_string = format!("{:0width$}", user_id, width = 20);
+    let prefix = &user_id_string[16..];
+    assert_eq!(prefix.len(), 4, "Prefix must be 4 characters long");
+
+    prefix.to_string()
+}
+
+/// Whether the file name of the path starts with the prefix of the user.
+pub fn has_user_prefix(path: &str, user_id: Snowflake) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    file_name.starts_with(&format!("{}_", user_prefix(user_id))) && !path.contains("..")
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// File extensions that uploads can be converted to, see [UploadValidator::conversion].
+const CONVERSION_TARGETS: [&str; 1] = ["webp"];
+
+/// Extensions whose content can be recognized from magic bytes, see [UploadValidator::check_content].
+const SNIFFED_EXTENSIONS: [&str; 16] = ["png", "jpg", "gif", "webp", "bmp", "tiff", "avif", "heif", "ico", "mp4", "webm", "mkv", "mov", "avi", "ogg", "mp3"];
+
+const DEFAULT_THUMBNAIL_NAME_TEMPLATE: &str = "{name}.{ext}.{width}.thumb.webp";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UploadOptions {
+    pub deduplicate: bool,
+    pub strip_metadata: bool,
+    pub thumbnail_widths: Vec<u32>,
+    pub thumbnail_name_template: String,
+    pub name_template: String,
+    pub name_normalization: NameNormalization,
+    pub webp_quality: u8,
+    pub remote_max_size: u64,
+    pub remote_timeout: Duration,
+}
+
+impl UploadOptions {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let upload = &config.upload;
+        let name_template = match (upload.random_names.unwrap_or(false), &upload.name_template) {
+            (true, Some(_)) => anyhow::bail!("Only one of UPLOAD_RANDOM_NAMES and UPLOAD_NAME_TEMPLATE can be set"),
+            (true, None) => RANDOM_NAME_TEMPLATE.to_string(),
+            (false, template) => template.clone().unwrap_or(DEFAULT_NAME_TEMPLATE.to_string()),
+        };
+        naming::validate(&name_template)?;
+        let thumbnail_name_template = upload.thumbnail_name_template.clone().unwrap_or(DEFAULT_THUMBNAIL_NAME_TEMPLATE.to_string());
+        // otherwise e.g. `a.png` and `a.jpg`, or two widths, would share thumbnails
+        if !thumbnail_name_template.contains("{ext}") || !thumbnail_name_template.contains("{width}") {
+            anyhow::bail!("Thumbnail name template must contain `{{ext}}` and `{{width}}`: {thumbnail_name_template}");
+        }
+        let name_normalization = match upload.name_normalization.as_deref() {
+            None | Some("reject") => NameNormalization::Reject,
+            Some("slugify") => NameNormalization::Slugify,
+            Some(other) => anyhow::bail!("Unknown name normalization: {other}"),
+        };
+
+        Ok(UploadOptions {
+            deduplicate: upload.deduplication.unwrap_or(true),
+            strip_metadata: upload.strip_metadata.unwrap_or(false),
+            thumbnail_widths: upload.thumbnail_widths.clone().unwrap_or_default(),
+            thumbnail_name_template,
+            name_template,
+            name_normalization,
+            webp_quality: upload.webp_quality.unwrap_or(80).min(100),
+            remote_max_size: upload.remote_max_size.unwrap_or(50_000_000),
+            remote_timeout: Duration::from_secs(upload.remote_timeout.unwrap_or(30)),
+        })
+    }
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct UploadValidator {
     frontend_url_max_length: Option<usize>,
+    /// Size limits per extension, and the allowed extensions if `restrict_file_types` is set.
     allowed_file_types: HashMap<String, Option<usize>>,
+    restrict_file_types: bool,
+    conversions: HashMap<String, String>,
 }
 
 impl UploadValidator {
-    pub fn from_env() -> anyhow::Result<Self> {
-        let frontend_url_max_length: Option<usize> = match env::var("FRONTEND_URL_MAX_LENGTH").ok() {
-            Some(s) => Some(s.parse::<usize>().context("Failed to parse FRONTEND_URL_MAX_LENGTH")?),
-            None => None
-        };
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        UploadValidator::new(config.upload.frontend_url_max_length, &config.upload.extensions, &config.upload.conversions)
+    }
 
-        let allowed_file_types: HashMap<String, Option<usize>> = match env::var("DISCORD_ALLOWED_FILE_EXTENSIONS").ok() {
-            None => HashMap::with_capacity(0),
-            Some(value) => {
-                let mut map = HashMap::with_capacity(value.split(',').count());
-                for file_type in value.split(',') {
-                    match file_type.split_once('=') {
-                        None => {
-                            map.insert(file_type.to_string(), None);
-                        },
-                        Some((ext, length_str)) => {
-                            let length = length_str.parse::<usize>().context("Failed to parse max length")?;
-                            map.insert(ext.to_string(), Some(length));
-                        }
-                    }
-                }
-                map
+    pub fn new(frontend_url_max_length: Option<usize>, extensions: &HashMap<String, ExtensionConfig>, conversion_rules: &HashMap<String, String>) -> anyhow::Result<Self> {
+        let allowed_file_types: HashMap<String, Option<usize>> = extensions.iter()
+            .map(|(ext, extension)| (ext.clone(), extension.max_size))
+            .collect();
+
+        let mut conversions: HashMap<String, String> = HashMap::new();
+        for (ext, target) in conversion_rules {
+            if target == "keep" || target == ext {
+                continue;
             }
-        };
+            if !CONVERSION_TARGETS.contains(&target.as_str()) {
+                anyhow::bail!("Unsupported conversion target for {ext}: {target}");
+            }
+            conversions.insert(ext.clone(), target.clone());
+        }
 
         Ok(UploadValidator {
             frontend_url_max_length,
+            restrict_file_types: !allowed_file_types.is_empty(),
             allowed_file_types,
+            conversions,
         })
     }
 
+    /// The extension files with the given extension are converted to, if any.
+    pub fn conversion(&self, extension: &str) -> Option<&str> {
+        self.conversions.get(extension).map(|s| s.as_str())
+    }
+
+    /// Whether files with the given extension may be uploaded at all.
+    pub fn allows(&self, extension: &str) -> bool {
+        !self.restrict_file_types || self.allowed_file_types.contains_key(extension)
+    }
+
+    /// The size limit of files with the given extension, if any.
+    pub fn max_size(&self, extension: &str) -> Option<usize> {
+        self.allowed_file_types.get(extension).copied().flatten()
+    }
+
+    /// Allows an extension with the given size limit, or only sets the limit if all file types are allowed anyway.
+    pub fn set_extension(&mut self, extension: &str, max_size: Option<usize>) {
+        self.allowed_file_types.insert(extension.to_string(), max_size);
+    }
+
+    /// Replaces the extension of the path if files of that type are converted.
+    pub fn converted_path(&self, path: &str) -> String {
+        match path.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() && !ext.contains('/') => match self.conversion(ext) {
+                Some(target) => format!("{stem}.{target}"),
+                None => path.to_string(),
+            },
+            _ => path.to_string(),
+        }
+    }
+
     pub fn check(&self, path: &str, original: &str, file_size: usize) -> Result<(), String> {
         let file_name = path.split('/').last().ok_or("Failed to get file name")?;
         let file_extension = file_name.split('.').last().ok_or("Invalid file name or extension")?;
@@ -48,7 +157,8 @@ impl UploadValidator {
         let original_file_name = original.split('/').last().ok_or("Failed to get attachment file name")?;
         let original_file_extension = original_file_name.split('.').last().ok_or("Invalid attachment file name or extension")?;
 
-        if file_extension != original_file_extension {
+        let expected_extension = self.conversion(original_file_extension).unwrap_or(original_file_extension);
+        if file_extension != expected_extension {
             return Err("Target file type does not match attachment file type".to_string());
         }
 
@@ -58,22 +168,85 @@ impl UploadValidator {
             }
         }
 
-        if !self.allowed_file_types.is_empty() {
-            let file_ext_str = file_extension;
+        let file_ext_str = original_file_extension;
 
-            match self.allowed_file_types.get(file_ext_str) {
-                Some(opt) => {
-                    if let Some(max_size) = opt {
-                        let max_file_size = *max_size;
-                        if file_size > max_file_size {
-                            return Err(format!("File too big! Maximum allowed size is {}", human_bytes::human_bytes(max_file_size as f64)));
-                        }
+        match self.allowed_file_types.get(file_ext_str) {
+            Some(opt) => {
+                if let Some(max_size) = opt {
+                    let max_file_size = *max_size;
+                    if file_size > max_file_size {
+                        return Err(format!("File too big! Maximum allowed size is {}", human_bytes::human_bytes(max_file_size as f64)));
                     }
                 }
-                None => return Err("File type not allowed!".to_string()),
+            }
+            None => {
+                if self.restrict_file_types {
+                    return Err("File type not allowed!".to_string());
+                }
             }
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Checks the magic bytes of the downloaded file against the extension of the attachment.
+    ///
+    /// Returns the detected MIME type, or `None` if the file type cannot be sniffed.
+    pub fn check_content(&self, original: &str, bytes: &[u8]) -> Result<Option<&'static str>, String> {
+        let original_file_name = original.split('/').last().ok_or("Failed to get attachment file name")?;
+        let original_file_extension = original_file_name.split('.').last().ok_or("Invalid attachment file name or extension")?;
+        let claimed = canonical_extension(original_file_extension);
+
+        match infer::get(bytes) {
+            // text formats like `xml` also match e.g. SVG images, so they only reject files claiming a binary type
+            Some(kind) if kind.matcher_type() == infer::MatcherType::Text && !SNIFFED_EXTENSIONS.contains(&claimed) => Ok(None),
+            Some(kind) => {
+                if canonical_extension(kind.extension()) != claimed {
+                    return Err(format!("File content does not match file type, detected `{}`", kind.extension()));
+                }
+                // ogg containers are detected as audio, even if they contain video
+                if original_file_extension == "ogv" {
+                    return Ok(Some("video/ogg"));
+                }
+                Ok(Some(kind.mime_type()))
+            }
+            None => {
+                if SNIFFED_EXTENSIONS.contains(&claimed) {
+                    return Err("Unrecognized file content".to_string());
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// The extension of a file from its magic bytes, or else from the `Content-Type` it was served with.
+pub fn detect_extension(bytes: &[u8], content_type: Option<&str>) -> Option<&'static str> {
+    if let Some(kind) = infer::get(bytes).filter(|kind| kind.matcher_type() != infer::MatcherType::Text) {
+        return Some(kind.extension());
+    }
+
+    let mime_type = content_type?.split(';').next()?.trim().to_ascii_lowercase();
+    match mime_type.as_str() {
+        "image/svg+xml" => Some("svg"),
+        "image/png" => Some("png"),
+        "image/jpeg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "video/mp4" => Some("mp4"),
+        "video/webm" => Some("webm"),
+        "text/plain" => Some("txt"),
+        _ => None,
+    }
+}
+
+fn canonical_extension(extension: &str) -> &str {
+    match extension {
+        "jpeg" | "jpe" | "jfif" => "jpg",
+        "tif" => "tiff",
+        "heic" => "heif",
+        "m4v" => "mp4",
+        "ogv" | "oga" | "opus" => "ogg",
+        other => other,
+    }
+}